@@ -0,0 +1,420 @@
+//! Registry of the game accounts played on one device.
+//!
+//! Each account has the ids it reports drops with, its default stages and recruit preferences,
+//! and its own `History` directory named after it.
+//!
+//! Reports are not saved by the registry: the caller follows a task's report as usual, and
+//! saves it to `AccountRegistry::history_of` the task. The account's recruit policy is
+//! carried out by `Account::follow_recruits`.
+//!
+//! ```toml
+//! [[accounts]]
+//! name = "main"
+//! account_name = "4567"
+//! penguin_id = "12345678"
+//! stages = ["CE-6", "1-7"]
+//!
+//! [[accounts]]
+//! name = "alt"
+//! account_name = "8910"
+//! recruit = { times = 4, expedite = false }
+//!
+//! [[accounts.recruit_policy.rules]]
+//! tag = "高级资深干员"
+//! ```
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::Events;
+use crate::binding::recruitment::RecruitCalculator;
+use crate::binding::tasks::{
+    ClientType, Fight, PartialAppend, Paused, PlanTask, Recruit, RecruitDecisionRecord,
+    RecruitPolicy, SanityPlan, Server, StartUp, TaskPlan,
+};
+use crate::history::History;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Account {
+    /// Unique name in the registry, also the name of its history directory
+    pub name: String,
+    /// Passed to `StartUp::set_account_name` to switch to this account
+    pub account_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub penguin_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yituliu_id: Option<String>,
+    /// Stages fought when a `Fight` has none, in order of preference
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<String>,
+    /// Replaces the `Recruit` tasks of a plan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recruit: Option<Recruit<Paused>>,
+    /// Decides on the tags of the account's `Recruit` tasks, see `follow_recruits`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recruit_policy: Option<RecruitPolicy>,
+}
+
+impl Account {
+    pub fn new(name: &str, account_name: &str) -> Self {
+        Account {
+            name: name.to_string(),
+            account_name: account_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn history<P: AsRef<Path>>(&self, root: P) -> Result<History> {
+        History::open(root, &self.name)
+    }
+
+    pub fn start_up(&self, client_type: ClientType) -> StartUp<Paused> {
+        StartUp::new_paused()
+            .set_client_type(client_type)
+            .set_start_game_enabled(true)
+            .set_account_name(&self.account_name)
+    }
+
+    /// `fight` reporting with the account's id, on its first default stage if it has none
+    pub fn fight(&self, mut fight: Fight<Paused>) -> Fight<Paused> {
+        if let Some(penguin_id) = &self.penguin_id {
+            fight = fight.report_to_penguin(penguin_id.clone());
+        }
+        match self.stages.first() {
+            Some(stage) if fight.stage_code().is_empty() => fight.stage(stage),
+            _ => fight,
+        }
+    }
+
+    /// The account's recruit preferences if any, else `recruit`, reporting with its ids
+    pub fn recruit(&self, recruit: Recruit<Paused>) -> Recruit<Paused> {
+        let mut recruit = self.recruit.clone().unwrap_or(recruit);
+        if let Some(penguin_id) = &self.penguin_id {
+            recruit = recruit.report_to_penguin(penguin_id.clone());
+        }
+        if let Some(yituliu_id) = &self.yituliu_id {
+            recruit = recruit.report_to_yituliu(yituliu_id.clone());
+        }
+        recruit
+    }
+
+    /// The default stages as a sanity plan, each one a fallback for the previous
+    pub fn sanity_plan(&self, server: Server) -> SanityPlan {
+        let fight = self.fight(Fight::new_paused());
+        self.stages
+            .iter()
+            .fold(SanityPlan::new(server).fight(fight), |plan, stage| {
+                plan.stage(stage)
+            })
+    }
+
+    /// `task` adapted to the account
+    pub fn apply(&self, task: &PlanTask) -> PlanTask {
+        match task {
+            PlanTask::StartUp(start_up) => {
+                PlanTask::StartUp(start_up.clone().set_account_name(&self.account_name))
+            }
+            PlanTask::Fight(fight) => PlanTask::Fight(self.fight(fight.clone())),
+            PlanTask::Recruit(recruit) => PlanTask::Recruit(self.recruit(recruit.clone())),
+            task => task.clone(),
+        }
+    }
+
+    /// The tasks of `plan` for this account, starting with switching to it if the plan has
+    /// no `StartUp` of its own
+    pub fn plan(&self, client_type: ClientType, plan: &TaskPlan) -> TaskPlan {
+        let mut account_plan = TaskPlan::new();
+        if !plan
+            .enabled()
+            .any(|task| matches!(task, PlanTask::StartUp(_)))
+        {
+            account_plan = account_plan.push(PlanTask::StartUp(self.start_up(client_type)));
+        }
+        plan.enabled()
+            .map(|task| self.apply(task))
+            .fold(account_plan, TaskPlan::push)
+    }
+
+    /// Carry out the recruit policy on the appended `Recruit` tasks of the account, in order,
+    /// recording the decisions to `history` if given. Nothing is done without a policy.
+    ///
+    /// Subscribe `events` before starting the connection, so that no event is missed.
+    pub async fn follow_recruits(
+        &self,
+        maa: &MAAConnection,
+        mut events: broadcast::Receiver<Events>,
+        tasks: &AccountTasks,
+        calculator: &RecruitCalculator,
+        history: Option<&History>,
+    ) -> Result<Vec<RecruitDecisionRecord>> {
+        let Some(policy) = &self.recruit_policy else {
+            return Ok(Vec::new());
+        };
+        let mut records = Vec::new();
+        for (id, base) in &tasks.recruits {
+            let send = |params: &Recruit<Paused>| maa.set_task_params(*id, params);
+            records.extend(
+                policy
+                    .follow_with(send, &mut events, calculator, base, *id, history)
+                    .await?,
+            );
+        }
+        Ok(records)
+    }
+}
+
+/// The ids of the tasks appended for one account
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountTasks {
+    pub account: String,
+    pub ids: Vec<usize>,
+    /// The `Recruit` tasks among them, as appended
+    pub recruits: Vec<(usize, Recruit<Paused>)>,
+}
+
+impl AccountTasks {
+    /// The tasks of `plan` appended as `ids`, in the order of its enabled tasks
+    pub fn new(account: &str, plan: &TaskPlan, ids: Vec<usize>) -> Self {
+        let recruits = plan
+            .enabled()
+            .zip(&ids)
+            .filter_map(|(task, id)| match task {
+                PlanTask::Recruit(recruit) => Some((*id, recruit.clone())),
+                _ => None,
+            })
+            .collect();
+        AccountTasks {
+            account: account.to_string(),
+            ids,
+            recruits,
+        }
+    }
+}
+
+/// Which account a task belongs to, so its results go to the right history
+pub fn account_of(appended: &[AccountTasks], task_id: usize) -> Option<&str> {
+    appended
+        .iter()
+        .find(|a| a.ids.contains(&task_id))
+        .map(|a| a.account.as_str())
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountRegistry {
+    pub accounts: Vec<Account>,
+}
+
+impl AccountRegistry {
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let registry: Self = toml::from_str(s)?;
+        registry.check()?;
+        Ok(registry)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        let registry: Self = serde_json::from_str(s)?;
+        registry.check()?;
+        Ok(registry)
+    }
+
+    /// Load a registry, the format is chosen by the extension (`.json` or `.toml`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let registry = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&content),
+            Some("toml") => Self::from_toml(&content),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown account registry format: {}",
+                    path.display()
+                ))
+            }
+        };
+        registry.map_err(|e| anyhow!("Invalid account registry {}: {e}", path.display()))
+    }
+
+    fn check(&self) -> Result<()> {
+        for (i, account) in self.accounts.iter().enumerate() {
+            if account.name.is_empty() {
+                return Err(anyhow!("Account {i} has no name"));
+            }
+            if self.accounts[..i].iter().any(|a| a.name == account.name) {
+                return Err(anyhow!("Duplicate account: {}", account.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.iter().find(|a| a.name == name)
+    }
+
+    /// The history under `root` of the account the task `task_id` was appended for
+    pub fn history_of<P: AsRef<Path>>(
+        &self,
+        root: P,
+        appended: &[AccountTasks],
+        task_id: usize,
+    ) -> Result<Option<History>> {
+        account_of(appended, task_id)
+            .and_then(|name| self.get(name))
+            .map(|account| account.history(root))
+            .transpose()
+    }
+
+    /// `plan` run once for every account, in order
+    pub fn plan(&self, client_type: ClientType, plan: &TaskPlan) -> TaskPlan {
+        let mut cycle = TaskPlan::new();
+        for account in &self.accounts {
+            cycle.tasks.extend(account.plan(client_type, plan).tasks);
+        }
+        cycle
+    }
+
+    /// Append `plan` for every account, returning the ids of each account's tasks.
+    ///
+    /// Every task is validated first, so an invalid plan appends nothing. If MaaCore refuses
+    /// a task anyway, the error is a [`PartialAppend`] with the ids of every account's tasks
+    /// already appended, as in `TaskPlan::append_in`.
+    pub fn append_in(
+        &self,
+        maa: &MAAConnection,
+        client_type: ClientType,
+        plan: &TaskPlan,
+    ) -> Result<Vec<AccountTasks>> {
+        let plans = self
+            .accounts
+            .iter()
+            .map(|account| (account, account.plan(client_type, plan)))
+            .collect::<Vec<_>>();
+        for (_, plan) in &plans {
+            for task in plan.enabled() {
+                task.validate(maa)?;
+            }
+        }
+        let mut appended: Vec<AccountTasks> = Vec::new();
+        for (account, plan) in plans {
+            match plan.append_in(maa) {
+                Ok(ids) => appended.push(AccountTasks::new(&account.name, &plan, ids)),
+                Err(error) => {
+                    let mut ids = appended
+                        .into_iter()
+                        .flat_map(|tasks| tasks.ids)
+                        .collect::<Vec<_>>();
+                    let error = match error.downcast::<PartialAppend>() {
+                        Ok(partial) => {
+                            ids.extend(partial.ids);
+                            partial.error
+                        }
+                        Err(error) => error,
+                    };
+                    if ids.is_empty() {
+                        return Err(error);
+                    }
+                    return Err(PartialAppend { ids, error }.into());
+                }
+            }
+        }
+        Ok(appended)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = AccountRegistry::from_toml(
+            r#"
+            [[accounts]]
+            name = "main"
+            account_name = "4567"
+            penguin_id = "12345678"
+            stages = ["CE-6", "1-7"]
+
+            [[accounts]]
+            name = "alt"
+            account_name = "8910"
+            recruit = { times = 4 }
+
+            [[accounts.recruit_policy.rules]]
+            tag = "高级资深干员"
+            "#,
+        )
+        .unwrap();
+        assert!(AccountRegistry::from_toml(
+            "[[accounts]]\nname = \"a\"\n[[accounts]]\nname = \"a\""
+        )
+        .is_err());
+        let main = registry.get("main").unwrap();
+        assert_eq!(
+            main.fight(Fight::new_paused()),
+            Fight::new_paused()
+                .report_to_penguin("12345678".to_string())
+                .stage("CE-6")
+        );
+        assert_eq!(
+            main.fight(Fight::new_paused().stage("LS-6")).stage_code(),
+            "LS-6"
+        );
+
+        let plan = TaskPlan::new()
+            .push(PlanTask::Fight(Fight::new_paused()))
+            .push(PlanTask::Recruit(Recruit::new_paused()));
+        let cycle = registry.plan(ClientType::Official, &plan);
+        assert_eq!(cycle.tasks.len(), 6);
+        let own = plan.clone().push(PlanTask::StartUp(StartUp::new_paused()));
+        assert_eq!(
+            main.plan(ClientType::Official, &own).tasks[2].task,
+            PlanTask::StartUp(StartUp::new_paused().set_account_name("4567"))
+        );
+        assert_eq!(
+            cycle.tasks[3].task,
+            PlanTask::StartUp(
+                StartUp::new_paused()
+                    .set_client_type(ClientType::Official)
+                    .set_start_game_enabled(true)
+                    .set_account_name("8910")
+            )
+        );
+        assert_eq!(
+            cycle.tasks[5].task,
+            PlanTask::Recruit(Recruit::new_paused().times(4))
+        );
+
+        let alt = registry.get("alt").unwrap();
+        assert_eq!(alt.recruit_policy.as_ref().unwrap().rules.len(), 1);
+        assert!(main.recruit_policy.is_none());
+
+        let appended = [
+            AccountTasks::new(
+                "main",
+                &main.plan(ClientType::Official, &plan),
+                vec![1, 2, 3],
+            ),
+            AccountTasks::new("alt", &alt.plan(ClientType::Official, &plan), vec![4, 5, 6]),
+        ];
+        assert_eq!(appended[1].recruits, [(6, Recruit::new_paused().times(4))]);
+        assert_eq!(account_of(&appended, 5), Some("alt"));
+        assert_eq!(account_of(&appended, 7), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(registry
+            .history_of(dir.path(), &appended, 2)
+            .unwrap()
+            .is_some());
+        assert!(dir.path().join("main").is_dir());
+        assert!(registry
+            .history_of(dir.path(), &appended, 7)
+            .unwrap()
+            .is_none());
+    }
+}
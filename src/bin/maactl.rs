@@ -0,0 +1,129 @@
+//! Command line client for the control socket of a running instance
+
+#[cfg(unix)]
+mod client {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    use anyhow::{anyhow, Result};
+    use serde_json::Value;
+
+    use maa_rust_ui::binding::recruitment::RecruitCalculator;
+    use maa_rust_ui::control::{default_socket_path, Method, Request, Response};
+
+    const USAGE: &str = "\
+Usage: maactl [-s SOCKET] <COMMAND>
+       maactl recruit <RESOURCE_DIR> <TAG>...
+
+Commands:
+  append <TYPE> [PARAMS]  Append a task, PARAMS is the MaaCore JSON params
+  start                   Start running the appended tasks
+  stop                    Stop running
+  status                  Show the connection status
+  screenshot <FILE>       Save a screenshot of the device to FILE
+  tail                    Print every event as it arrives
+  recruit                 Show the recruit tag combinations, without a running instance";
+
+    fn parse_args(mut args: Vec<String>) -> Result<(PathBuf, Method)> {
+        let mut socket = default_socket_path();
+        if args.first().map(|s| s.as_str()) == Some("-s") {
+            if args.len() < 2 {
+                return Err(anyhow!("Missing socket path"));
+            }
+            socket = PathBuf::from(args.remove(1));
+            args.remove(0);
+        }
+        let mut args = args.into_iter();
+        let method = match args.next().as_deref() {
+            Some("append") => {
+                let type_ = args.next().ok_or_else(|| anyhow!("Missing task type"))?;
+                let params = match args.next() {
+                    Some(params) => serde_json::from_str(&params)?,
+                    None => Value::Null,
+                };
+                Method::AppendTask { type_, params }
+            }
+            Some("start") => Method::Start,
+            Some("stop") => Method::Stop,
+            Some("status") => Method::Status,
+            Some("screenshot") => {
+                let path = args.next().ok_or_else(|| anyhow!("Missing file path"))?;
+                // The server writes the file, so hand it an absolute path
+                let path = std::env::current_dir()?.join(path);
+                Method::Screenshot {
+                    path: path.to_string_lossy().to_string(),
+                }
+            }
+            Some("tail") => Method::TailEvents,
+            Some(other) => return Err(anyhow!("Unknown command: {other}")),
+            None => return Err(anyhow!("Missing command")),
+        };
+        Ok((socket, method))
+    }
+
+    fn recruit(args: &[String]) -> Result<()> {
+        let (resource_dir, tags) = args
+            .split_first()
+            .ok_or_else(|| anyhow!("Missing resource directory"))?;
+        if tags.is_empty() {
+            return Err(anyhow!("Missing recruit tags"));
+        }
+        let calculator = RecruitCalculator::load(resource_dir)?;
+        let tags = tags.iter().map(String::as_str).collect::<Vec<_>>();
+        let combinations = calculator.combinations(&tags)?;
+        println!("{}", serde_json::to_string_pretty(&combinations)?);
+        Ok(())
+    }
+
+    pub fn run() -> Result<()> {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        if args.first().map(|s| s.as_str()) == Some("recruit") {
+            return recruit(&args[1..]).map_err(|e| anyhow!("{e}\n\n{USAGE}"));
+        }
+        let (socket, method) = match parse_args(args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(anyhow!("{e}\n\n{USAGE}")),
+        };
+        let tail = method == Method::TailEvents;
+
+        let mut stream = UnixStream::connect(&socket)
+            .map_err(|e| anyhow!("Failed to connect to {}: {e}", socket.display()))?;
+        let mut request = serde_json::to_string(&Request::new(1, method))?;
+        request.push('\n');
+        stream.write_all(request.as_bytes())?;
+
+        let mut lines = BufReader::new(stream).lines();
+        let line = lines.next().ok_or_else(|| anyhow!("No response"))??;
+        let response: Response = serde_json::from_str(&line)?;
+        if let Some(error) = response.error {
+            return Err(anyhow!("{} ({})", error.message, error.code));
+        }
+        if !tail {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.result.unwrap_or_default())?
+            );
+            return Ok(());
+        }
+        for line in lines {
+            let notification: Value = serde_json::from_str(&line?)?;
+            println!("{}", notification["params"]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn main() {
+    if let Err(e) = client::run() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("maactl is only available on unix");
+    std::process::exit(1);
+}
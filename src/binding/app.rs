@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::binding::connection::{find_it, MAAConnection};
+use crate::binding::tasks::ClientType;
+
+/// Control of the game client on a device through adb, without MaaCore
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppControl {
+    adb: PathBuf,
+    serial: String,
+}
+
+impl AppControl {
+    pub fn new<P: AsRef<Path>>(adb: P, serial: &str) -> Self {
+        AppControl {
+            adb: adb.as_ref().to_path_buf(),
+            serial: serial.to_string(),
+        }
+    }
+
+    /// Use the `adb` found in `PATH`
+    pub fn find(serial: &str) -> Result<Self> {
+        let adb = find_it("adb").ok_or_else(|| anyhow!("adb not found in PATH"))?;
+        Ok(Self::new(adb, serial))
+    }
+
+    /// The device `maa` is connected to
+    pub fn for_connection(maa: &MAAConnection) -> Result<Self> {
+        Self::find(maa.target())
+    }
+
+    async fn shell(&self, args: &[&str]) -> Result<String> {
+        debug!("adb -s {} shell {}", self.serial, args.join(" "));
+        let output = Command::new(&self.adb)
+            .arg("-s")
+            .arg(&self.serial)
+            .arg("shell")
+            .args(args)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "adb shell {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    pub async fn launch(&self, client: ClientType) -> Result<()> {
+        info!("Launching {}", client.package());
+        let component = format!("{}/{}", client.package(), client.activity());
+        self.shell(&["am", "start", "-n", &component]).await?;
+        Ok(())
+    }
+
+    pub async fn force_stop(&self, client: ClientType) -> Result<()> {
+        info!("Stopping {}", client.package());
+        self.shell(&["am", "force-stop", client.package()]).await?;
+        Ok(())
+    }
+
+    /// Remove the external cache of the client, the internal one needs root
+    pub async fn clear_cache(&self, client: ClientType) -> Result<()> {
+        info!("Clearing the cache of {}", client.package());
+        let cache = format!("/sdcard/Android/data/{}/cache", client.package());
+        self.shell(&["rm", "-rf", &cache]).await?;
+        Ok(())
+    }
+
+    pub async fn is_foreground(&self, client: ClientType) -> Result<bool> {
+        let dump = self.shell(&["dumpsys", "window"]).await?;
+        Ok(focused_package(&dump) == Some(client.package()))
+    }
+
+    /// Wait until the client is in the foreground, checking every 2 seconds
+    pub async fn wait_foreground(&self, client: ClientType, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        while !self.is_foreground(client).await? {
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "{} is not in the foreground after {}s",
+                    client.package(),
+                    timeout.as_secs()
+                ));
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        Ok(())
+    }
+
+    pub async fn run(&self, step: &AppStep) -> Result<()> {
+        let client = ClientType::try_from(step.client_type.as_str())?;
+        match step.action {
+            AppAction::Launch => self.launch(client).await,
+            AppAction::ForceStop => self.force_stop(client).await,
+            AppAction::ClearCache => self.clear_cache(client).await,
+            AppAction::WaitForeground => {
+                self.wait_foreground(client, Duration::from_secs(step.timeout))
+                    .await
+            }
+        }
+    }
+}
+
+/// The package of the focused window in the output of `dumpsys window`
+pub fn focused_package(dump: &str) -> Option<&str> {
+    dump.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("mCurrentFocus=") || line.starts_with("mFocusedApp="))
+        .find_map(|line| {
+            let component = line.split_whitespace().find(|word| word.contains('/'))?;
+            component.split('/').next()
+        })
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppAction {
+    /// 启动客户端
+    #[default]
+    Launch,
+    /// 强制停止客户端
+    ForceStop,
+    /// 清理客户端的外部缓存
+    ClearCache,
+    /// 等待客户端进入前台
+    WaitForeground,
+}
+
+fn default_timeout() -> u64 {
+    120
+}
+
+/// 在任务计划中通过 adb 控制客户端的一步
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct AppStep {
+    pub action: AppAction,
+    /// 客户端版本，同 `ClientType::as_ref`
+    pub client_type: String,
+    /// `WaitForeground` 的超时，单位为秒，默认 120
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+impl Default for AppStep {
+    fn default() -> Self {
+        AppStep {
+            action: AppAction::default(),
+            client_type: ClientType::Official.as_ref().to_string(),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+impl AppStep {
+    pub fn new(action: AppAction, client_type: ClientType) -> Self {
+        AppStep {
+            action,
+            client_type: client_type.as_ref().to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ClientType::try_from(self.client_type.as_str())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_focused_package() {
+        let dump = "
+  mHoldScreenWindow=null
+  mCurrentFocus=Window{4d1c2e9 u0 com.hypergryph.arknights/com.u8.sdk.U8UnityContext}
+  mFocusedApp=ActivityRecord{9c1f3a0 u0 com.hypergryph.arknights/com.u8.sdk.U8UnityContext t12}
+";
+        assert_eq!(focused_package(dump), Some(ClientType::Official.package()));
+        assert_eq!(focused_package("  mCurrentFocus=null"), None);
+    }
+
+    #[test]
+    fn test_client_package() {
+        assert_eq!(
+            ClientType::try_from("twxy").unwrap().package(),
+            "tw.txwy.and.arknights"
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let step: AppStep =
+            serde_json::from_str(r#"{"action": "wait_foreground", "client_type": "YoStarEN"}"#)
+                .unwrap();
+        assert_eq!(
+            step,
+            AppStep::new(AppAction::WaitForeground, ClientType::YoStarEN)
+        );
+        assert!(AppStep::new(AppAction::Launch, ClientType::Bilibili)
+            .validate()
+            .is_ok());
+        let step = AppStep {
+            client_type: "Steam".to_string(),
+            ..Default::default()
+        };
+        assert!(step.validate().is_err());
+    }
+}
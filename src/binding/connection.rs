@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ffi::{c_void, CStr, CString};
 use std::path::{Path, PathBuf};
@@ -8,29 +8,44 @@ use std::task::{Context, Poll};
 
 use anyhow::{anyhow, Result};
 use futures::Future;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
 use serde_json::Value;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 use crate::binding::bind::*;
 use crate::binding::event_handler::{maa_callback, CALLBACK_CHANNEL};
 use crate::binding::events::*;
 use crate::binding::options::MAAOption;
-use crate::binding::resources::ItemMap;
-use crate::binding::tasks::StoppedTask;
+use crate::binding::resources::{
+    global_resource_dir, has_task_name, load_item_map, load_task_names, Item, ItemLookup, ItemMap,
+};
+use crate::binding::stages::StageCatalog;
+use crate::binding::tasks::{Server, StoppedTask};
+
+/// Large enough for an encoded 1080p screenshot, doubled until the image fits
+const SCREENSHOT_BUFFER_SIZE: usize = 1920 * 1080 * 3;
+/// Larger than any screenshot, for an 8K raw image
+const SCREENSHOT_BUFFER_MAX: usize = 7680 * 4320 * 4;
 
 #[derive(Debug)]
 pub struct MAAConnection {
     handle: AsstHandle,
     uuid: Arc<Mutex<Option<String>>>,
     target: String,
+    resources_path: PathBuf,
     id: i64,
     pub wakes: Arc<std::sync::Mutex<HashMap<i32, Value>>>,
     finished: Arc<Mutex<bool>>,
+    events: broadcast::Sender<Events>,
     item_map: ItemMap,
+    global_item_maps: HashMap<Server, ItemMap>,
+    task_names: HashSet<String>,
+    /// The error if `stages.json` could not be loaded, only `Fight` needs it
+    stages: Result<StageCatalog>,
 }
 
-fn find_it<P>(exe_name: P) -> Option<PathBuf>
+pub(crate) fn find_it<P>(exe_name: P) -> Option<PathBuf>
 where
     P: AsRef<Path>,
 {
@@ -171,17 +186,39 @@ impl<'a> MAABuilder<'a> {
         info!("Loading resources to {}", self.resources_path.display());
         Self::load_resource(&self.resources_path)?;
 
-        let item_map = self.resources_path.join("resource").join("item_index.json");
-        if !item_map.is_file() {
-            error!("Item index not found");
-            return Err(anyhow!("Item index not found"));
+        let item_map = load_item_map(self.resources_path.join("resource")).map_err(|e| {
+            error!("{}", e);
+            e
+        })?;
+        let mut global_item_maps = HashMap::new();
+        for server in [Server::US, Server::JP, Server::KR] {
+            let Some(dir) = global_resource_dir(server) else {
+                continue;
+            };
+            let dir = self.resources_path.join("resource/global").join(dir);
+            if let Ok(items) = load_item_map(dir.join("resource")) {
+                global_item_maps.insert(server, items);
+            }
+        }
+
+        let mut task_names = load_task_names(self.resources_path.join("resource"))?;
+        let mut stages = StageCatalog::load(self.resources_path.join("resource"));
+        if let Err(e) = &stages {
+            warn!("Fight stages will not be validated: {}", e);
         }
-        let item_map = std::fs::read_to_string(item_map)?;
-        let item_map: ItemMap = serde_json::from_str(&item_map)?;
 
         if let Some(path) = &self.incremental_path {
             info!("Loading incremental resources to {}", path.display());
             Self::load_resource(path)?;
+            task_names.extend(load_task_names(path.join("resource"))?);
+            if let Ok(stages) = &mut stages {
+                if path.join("resource").join("stages.json").is_file() {
+                    match StageCatalog::load(path.join("resource")) {
+                        Ok(incremental) => stages.extend(incremental),
+                        Err(e) => warn!("Incremental stages are not loaded: {}", e),
+                    }
+                }
+            }
         }
 
         info!("Creating connection to {}", self.adb_address);
@@ -192,10 +229,15 @@ impl<'a> MAABuilder<'a> {
             handle,
             uuid: Arc::new(Mutex::new(None)),
             target: self.adb_address.to_string(),
+            resources_path: self.resources_path.clone(),
             id,
             wakes: Arc::new(std::sync::Mutex::new(HashMap::new())),
             finished: Arc::new(Mutex::new(false)),
+            events: broadcast::channel(256).0,
             item_map,
+            global_item_maps,
+            task_names,
+            stages,
         };
         let settings = self.maa_settings.to_map();
         for (k, v) in settings {
@@ -258,6 +300,7 @@ impl MAAConnection {
         let wakes = self.wakes.clone();
         let uuid = self.uuid.clone();
         let finish = self.finished.clone();
+        let events = self.events.clone();
         tokio::spawn(async move {
             info!("Polling started");
             loop {
@@ -266,6 +309,8 @@ impl MAAConnection {
                 if *finished {
                     break;
                 }
+                // Nobody listening is fine, the event is handled below anyway
+                let _ = events.send(resp.clone());
                 match resp.type_ {
                     AsstMsg::InternalError => trace!("Received: {:?}", resp),
                     AsstMsg::InitFailed => handle_init_failed(resp.params).await,
@@ -287,12 +332,37 @@ impl MAAConnection {
         });
     }
 
+    /// Append a task, returning its id.
+    ///
+    /// MaaCore gives the id 0 to a task it refuses, such as one with invalid params. That id used
+    /// to be returned as is, it is an error now.
     pub fn append_task<'a>(&self, task: &impl StoppedTask<'a>) -> Result<usize> {
-        let id = CString::new(task.name())?;
-        let c_task = CString::new(task.to_json())?;
-        debug!("Appending task: {}", task.name());
+        self.append_raw_task(task.name(), &task.to_json())
+    }
+
+    /// Append a task by its MaaCore type name and raw JSON params, without any typed builder.
+    /// A refused task is an error, as in `append_task`
+    pub fn append_raw_task(&self, name: &str, params: &str) -> Result<usize> {
+        let id = CString::new(name)?;
+        let c_task = CString::new(params)?;
+        debug!("Appending task: {}", name);
         let ret = unsafe { AsstAppendTask(self.handle, id.as_ptr(), c_task.as_ptr()) };
-        Ok(ret as usize)
+        match ret {
+            0 => Err(anyhow!("Failed to append task: {name}")),
+            _ => Ok(ret as usize),
+        }
+    }
+
+    /// Replace the params of an appended task, only the params supported while running take
+    /// effect once the task has started
+    pub fn set_task_params<T: Serialize>(&self, id: usize, params: &T) -> Result<()> {
+        let c_params = CString::new(serde_json::to_string(params)?)?;
+        debug!("Setting params of task {}", id);
+        let ret = unsafe { AsstSetTaskParams(self.handle, id as AsstTaskId, c_params.as_ptr()) };
+        match ret {
+            1 => Ok(()),
+            _ => Err(anyhow!("Failed to set params of task {id}")),
+        }
     }
 
     pub fn start(&self) -> Result<()> {
@@ -317,6 +387,95 @@ impl MAAConnection {
         }
     }
 
+    /// Items of `resource/item_index.json`, keyed by item id
+    pub fn item_map(&self) -> &ItemMap {
+        &self.item_map
+    }
+
+    /// Items with their names localized for `server`, falling back to the CN names
+    pub fn items(&self, server: Server) -> &ItemMap {
+        self.global_item_maps.get(&server).unwrap_or(&self.item_map)
+    }
+
+    /// The item named exactly `name` on `server`, an error if several items share the name
+    pub fn find_item(&self, name: &str, server: Server) -> Result<Option<(&str, &Item)>> {
+        self.items(server).find_by_name(name)
+    }
+
+    /// Items whose name on `server` contains `query`, ignoring case and spaces
+    pub fn search_items(&self, query: &str, server: Server) -> Vec<(&str, &Item)> {
+        self.items(server).search(query)
+    }
+
+    /// Stages of `resource/stages.json`, with the incremental ones. An error if the stages
+    /// could not be loaded when connecting
+    pub fn stages(&self) -> Result<&StageCatalog> {
+        self.stages
+            .as_ref()
+            .map_err(|e| anyhow!("No stage data: {e}"))
+    }
+
+    /// Whether the loaded resources define the pipeline task `name`.
+    ///
+    /// `Prefix@Task` is a valid name as long as `Task` is defined, MaaCore derives it from `Task`
+    pub fn has_task(&self, name: &str) -> bool {
+        has_task_name(&self.task_names, name)
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The path given to `MAABuilder`, holding the `resource` directory
+    pub fn resources_path(&self) -> &Path {
+        &self.resources_path
+    }
+
+    pub async fn uuid(&self) -> Option<String> {
+        self.uuid.lock().await.clone()
+    }
+
+    /// Subscribe to every event received from MaaCore, in the order they arrive
+    pub fn subscribe(&self) -> broadcast::Receiver<Events> {
+        self.events.subscribe()
+    }
+
+    /// Take a screenshot of the device, returns the encoded image as given by MaaCore
+    pub async fn screenshot(&self) -> Result<Vec<u8>> {
+        let async_id = unsafe { AsstAsyncScreencap(self.handle, 1) };
+        if async_id == 0 {
+            return Err(anyhow!("Failed to request screencap"));
+        }
+        let ret = CallbackWatcher {
+            id: async_id,
+            wakes: self.wakes.clone(),
+        }
+        .await;
+        if ret != Value::Bool(true) {
+            return Err(anyhow!("Screencap failed: {ret}"));
+        }
+
+        // MaaCore gives no size when the buffer is too small, so grow it until the image fits
+        let mut buff = vec![0u8; SCREENSHOT_BUFFER_SIZE];
+        loop {
+            let size = unsafe {
+                AsstGetImage(
+                    self.handle,
+                    buff.as_mut_ptr() as *mut c_void,
+                    buff.len() as AsstSize,
+                )
+            };
+            if size != unsafe { AsstGetNullSize() } {
+                buff.truncate(size as usize);
+                return Ok(buff);
+            }
+            if buff.len() >= SCREENSHOT_BUFFER_MAX {
+                return Err(anyhow!("Failed to get image"));
+            }
+            buff.resize(buff.len() * 2, 0);
+        }
+    }
+
     pub async fn destroy(self) {
         let mut finish = self.finished.lock().await;
         *finish = true;
@@ -8,7 +8,7 @@ use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct AsyncCallInfo {
+pub struct AsyncCallInfo {
     pub uuid: String,
     pub what: String,
     pub async_call_id: i32,
@@ -17,7 +17,7 @@ struct AsyncCallInfo {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct AsyncCallInfoDetails {
+pub struct AsyncCallInfoDetails {
     pub ret: Value,
     pub cost: i32,
 }
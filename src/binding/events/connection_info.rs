@@ -6,7 +6,8 @@ use serde::Serialize;
 use serde_json::Value;
 use tokio::sync::Mutex;
 
-enum ConnectionInfoWhat {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionInfoWhat {
     ConnectFailed,
     Connected,
     UuidGot,
@@ -45,7 +46,7 @@ impl From<&str> for ConnectionInfoWhat {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct ConnectionInfo {
+pub struct ConnectionInfo {
     pub what: String,
     pub why: Option<String>,
     pub uuid: String,
@@ -54,7 +55,7 @@ struct ConnectionInfo {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct ConnectionInfoDetails {
+pub struct ConnectionInfoDetails {
     pub adb: String,
     pub address: String,
     pub config: String,
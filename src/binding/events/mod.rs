@@ -19,7 +19,7 @@ mod task_chain_completed;
 mod task_chain_error;
 mod task_chain_start;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AsstMsg {
     // 内部错误
     InternalError = 0,
@@ -57,7 +57,7 @@ pub enum AsstMsg {
     SubTaskStopped = 20004,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Events {
     pub type_: AsstMsg,
     pub params: Value,
@@ -1,40 +1,66 @@
-use log::{info, trace, warn};
+use log::{error, info, trace, warn};
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct SubTaskExtraInfo {
-    class: String,
-    details: Details,
+pub struct SubTaskExtraInfo {
+    pub class: String,
+    pub details: Details,
     #[serde(default)]
-    first: Vec<String>,
-    pre_task: Option<String>,
-    subtask: String,
-    taskchain: String,
-    taskid: i64,
-    uuid: String,
-    what: String,
+    pub first: Vec<String>,
+    pub pre_task: Option<String>,
+    pub subtask: String,
+    pub taskchain: String,
+    pub taskid: i64,
+    pub uuid: String,
+    pub what: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct Details {
-    exec_times: Option<i64>,
-    limit_type: Option<String>,
-    max_times: Option<i64>,
-    task: Option<String>,
-    drops: Option<Vec<Drop>>,
-    stage: Option<Stage>,
-    stars: Option<i64>,
+pub struct Details {
+    pub exec_times: Option<i64>,
+    pub limit_type: Option<String>,
+    pub max_times: Option<i64>,
+    pub task: Option<String>,
+    pub drops: Option<Vec<StageDrop>>,
+    pub stage: Option<Stage>,
+    pub stars: Option<i64>,
     #[serde(default)]
-    stats: Vec<Stat>,
+    pub stats: Vec<Stat>,
     #[serde(default)]
-    tags: Vec<String>,
-    level: Option<i64>,
+    pub tags: Vec<String>,
+    pub level: Option<i64>,
+    #[serde(default)]
+    pub result: Vec<Result>,
+    pub count: Option<i64>,
+    pub is_expiring: Option<bool>,
+    pub current_sanity: Option<i64>,
+    pub max_sanity: Option<i64>,
+    pub facility: Option<String>,
+    pub index: Option<i64>,
+    pub product: Option<String>,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub doc: Option<String>,
+    #[serde(default)]
+    pub formation: Vec<String>,
+    pub selected: Option<String>,
+    pub done: Option<bool>,
+    pub arkplanner: Option<Value>,
+    pub lolicon: Option<Value>,
     #[serde(default)]
-    result: Vec<Result>,
+    pub all_oper: Vec<BoxOperator>,
+    #[serde(default)]
+    pub own_opers: Vec<BoxOperator>,
+    pub total: Option<i64>,
+    pub deposit: Option<i64>,
+    pub floor: Option<i64>,
+    pub game_pass: Option<bool>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -52,29 +78,41 @@ pub struct Operator {
     pub name: String,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoxOperator {
+    pub id: String,
+    pub name: String,
+    pub own: bool,
+    pub rarity: i64,
+    pub elite: i64,
+    pub level: i64,
+    pub potential: i64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Drop {
-    drop_type: String,
-    item_id: String,
-    item_name: String,
-    quantity: i64,
+pub struct StageDrop {
+    pub drop_type: String,
+    pub item_id: String,
+    pub item_name: String,
+    pub quantity: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Stage {
-    stage_code: String,
-    stage_id: String,
+pub struct Stage {
+    pub stage_code: String,
+    pub stage_id: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Stat {
-    add_quantity: i64,
-    item_id: String,
-    item_name: String,
-    quantity: i64,
+pub struct Stat {
+    pub add_quantity: i64,
+    pub item_id: String,
+    pub item_name: String,
+    pub quantity: i64,
 }
 
 pub async fn handle_sub_task_extra_info(params: Value) {
@@ -140,6 +178,16 @@ pub async fn handle_sub_task_extra_info(params: Value) {
                 info!("Recruit star level: {}", recruit_star_level);
             }
         }
+        _ if async_call_info.what == "UnsupportedLevel" => {
+            error!("Copilot does not support this level")
+        }
+        _ if async_call_info.what == "CopilotAction" => {
+            info!(
+                "Copilot action: {} {}",
+                async_call_info.details.action.unwrap_or_default(),
+                async_call_info.details.target.unwrap_or_default()
+            )
+        }
         _ => {
             trace!("sub_task_extra_info: {:?}", async_call_info)
         }
@@ -5,26 +5,26 @@ use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-struct SubTaskStart {
-    class: String,
-    details: SubTaskStartDetails,
+pub struct SubTaskStart {
+    pub class: String,
+    pub details: SubTaskStartDetails,
     #[serde(default)]
-    first: Vec<String>,
-    pre_task: Option<String>,
-    subtask: String,
-    taskchain: String,
-    taskid: i64,
-    uuid: String,
+    pub first: Vec<String>,
+    pub pre_task: Option<String>,
+    pub subtask: String,
+    pub taskchain: String,
+    pub taskid: i64,
+    pub uuid: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct SubTaskStartDetails {
-    action: Option<String>,
-    algorithm: Option<String>,
-    exec_times: Option<i64>,
-    max_times: Option<i64>,
-    task: Option<String>,
+#[serde(rename_all = "snake_case")]
+pub struct SubTaskStartDetails {
+    pub action: Option<String>,
+    pub algorithm: Option<String>,
+    pub exec_times: Option<i64>,
+    pub max_times: Option<i64>,
+    pub task: Option<String>,
 }
 
 pub async fn handle_sub_task_start(params: Value) {
@@ -5,10 +5,10 @@ use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct TaskChainCompleted {
-    taskchain: String,
-    taskid: i64,
-    uuid: String,
+pub struct TaskChainCompleted {
+    pub taskchain: String,
+    pub taskid: i64,
+    pub uuid: String,
 }
 
 pub async fn handle_task_chain_completed(params: Value) {
@@ -1,7 +1,10 @@
+pub mod app;
 mod bind;
 pub mod connection;
 pub mod event_handler;
-mod events;
+pub mod events;
 pub mod options;
-mod resources;
+pub mod recruitment;
+pub mod resources;
+pub mod stages;
 pub mod tasks;
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Tag of the 6 star operators, they can only be recruited with it
+pub const TOP_OPERATOR: &str = "高级资深干员";
+/// Tag of the 5 star operators
+pub const SENIOR_OPERATOR: &str = "资深干员";
+
+/// Tags that can be selected at once
+const MAX_SELECTED: usize = 3;
+/// Tags shown on a recruitment slot
+const MAX_SHOWN: usize = 5;
+
+/// An operator of `recruitment.json`, tags are the Chinese ones
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecruitOperator {
+    pub id: String,
+    pub name: String,
+    pub rarity: i64,
+    pub tags: Vec<String>,
+}
+
+impl RecruitOperator {
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+            || (tag == TOP_OPERATOR && self.rarity == 6)
+            || (tag == SENIOR_OPERATOR && self.rarity == 5)
+    }
+}
+
+/// Selected tags and the operators they can give
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecruitCombination {
+    /// Chinese tags
+    pub tags: Vec<String>,
+    /// The lowest star level that can be recruited, with the longest time of 9 hours
+    pub level: i64,
+    /// By descending rarity
+    pub operators: Vec<RecruitOperator>,
+}
+
+/// Offline recruitment calculator, from `resource/recruitment.json`
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RecruitCalculator {
+    /// Chinese tag to the name shown by the client
+    tags: HashMap<String, String>,
+    operators: Vec<RecruitOperator>,
+}
+
+impl RecruitCalculator {
+    pub fn new(operators: Vec<RecruitOperator>, tags: HashMap<String, String>) -> Self {
+        RecruitCalculator { tags, operators }
+    }
+
+    pub fn load<P: AsRef<Path>>(resource_dir: P) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Recruitment {
+            #[serde(default)]
+            tags: HashMap<String, String>,
+            operators: Vec<RecruitOperator>,
+        }
+
+        let path = resource_dir.as_ref().join("recruitment.json");
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        let data: Recruitment = serde_json::from_str(&data)?;
+        Ok(Self::new(data.operators, data.tags))
+    }
+
+    pub fn operators(&self) -> &[RecruitOperator] {
+        &self.operators
+    }
+
+    /// The Chinese tag of a Chinese or localized tag name
+    pub fn resolve_tag<'a>(&'a self, tag: &'a str) -> Result<&'a str> {
+        if let Some((chinese, _)) = self.tags.get_key_value(tag) {
+            return Ok(chinese);
+        }
+        if let Some((chinese, _)) = self
+            .tags
+            .iter()
+            .find(|(_, local)| local.to_lowercase() == tag.to_lowercase())
+        {
+            return Ok(chinese);
+        }
+        if tag == TOP_OPERATOR
+            || tag == SENIOR_OPERATOR
+            || self.operators.iter().any(|o| o.has_tag(tag))
+        {
+            return Ok(tag);
+        }
+        Err(anyhow!("Unknown recruit tag: {tag}"))
+    }
+
+    /// The name of a Chinese tag shown by the client
+    pub fn local_tag<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.tags.get(tag).map(String::as_str).unwrap_or(tag)
+    }
+
+    /// The operators and guaranteed level of `tags` selected together
+    pub fn combination(&self, tags: &[&str]) -> Option<RecruitCombination> {
+        let mut operators = self
+            .operators
+            .iter()
+            .filter(|o| tags.iter().all(|t| o.has_tag(t)))
+            .filter(|o| o.rarity < 6 || tags.contains(&TOP_OPERATOR))
+            .cloned()
+            .collect::<Vec<_>>();
+        if operators.is_empty() {
+            return None;
+        }
+        operators.sort_by(|a, b| b.rarity.cmp(&a.rarity).then(a.name.cmp(&b.name)));
+        // 1 and 2 star operators can't be recruited in 9 hours, unless the tags give nothing else
+        let level = operators
+            .iter()
+            .map(|o| o.rarity)
+            .filter(|r| *r >= 3)
+            .min()
+            .or_else(|| operators.iter().map(|o| o.rarity).min())
+            .unwrap_or_default();
+        Some(RecruitCombination {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            level,
+            operators,
+        })
+    }
+
+    /// Every combination of up to 3 of the shown tags that gives an operator, best first
+    pub fn combinations(&self, shown: &[&str]) -> Result<Vec<RecruitCombination>> {
+        if shown.len() > MAX_SHOWN {
+            return Err(anyhow!("At most {MAX_SHOWN} recruit tags are shown"));
+        }
+        let tags = shown
+            .iter()
+            .map(|t| self.resolve_tag(t))
+            .collect::<Result<Vec<_>>>()?;
+        let mut combinations = Vec::new();
+        for mask in 1..1usize << tags.len() {
+            if mask.count_ones() as usize > MAX_SELECTED {
+                continue;
+            }
+            let selected = (0..tags.len())
+                .filter(|i| mask & (1 << i) != 0)
+                .map(|i| tags[i])
+                .collect::<Vec<_>>();
+            combinations.extend(self.combination(&selected));
+        }
+        combinations.sort_by(|a, b| {
+            b.level
+                .cmp(&a.level)
+                .then(a.tags.len().cmp(&b.tags.len()))
+                .then(a.operators.len().cmp(&b.operators.len()))
+        });
+        Ok(combinations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_combinations() {
+        let operator = |name: &str, rarity, tags: &[&str]| RecruitOperator {
+            id: name.to_lowercase(),
+            name: name.to_string(),
+            rarity,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        let calculator = RecruitCalculator::new(
+            vec![
+                operator("Lancet-2", 1, &["医疗干员", "远程位", "治疗", "支援机械"]),
+                operator("Ansel", 3, &["医疗干员", "远程位", "治疗"]),
+                operator("Myrrh", 4, &["医疗干员", "远程位", "治疗"]),
+                operator("Ptilopsis", 5, &["医疗干员", "远程位", "治疗", "支援"]),
+                operator("Shining", 6, &["医疗干员", "远程位", "治疗", "支援"]),
+                operator(
+                    "Projekt Red",
+                    4,
+                    &["特种干员", "近战位", "快速复活", "控场"],
+                ),
+            ],
+            HashMap::from([
+                ("医疗干员".to_string(), "Medic".to_string()),
+                ("支援".to_string(), "Support".to_string()),
+            ]),
+        );
+
+        assert_eq!(calculator.resolve_tag("medic").unwrap(), "医疗干员");
+        assert!(calculator.resolve_tag("Nuker").is_err());
+        assert!(calculator
+            .combinations(&["a", "b", "c", "d", "e", "f"])
+            .is_err());
+
+        let combinations = calculator
+            .combinations(&["Medic", "Support", "治疗"])
+            .unwrap();
+        // Shining needs the top operator tag, so support alone guarantees Ptilopsis
+        assert_eq!(combinations[0].tags, ["支援"]);
+        assert_eq!(combinations[0].level, 5);
+        assert_eq!(combinations[0].operators.len(), 1);
+        assert_eq!(combinations.len(), 7);
+        assert_eq!(calculator.combination(&["医疗干员"]).unwrap().level, 3);
+        assert_eq!(calculator.combination(&["支援机械"]).unwrap().level, 1);
+
+        let top = calculator.combination(&[TOP_OPERATOR]).unwrap();
+        assert_eq!(top.level, 6);
+        assert_eq!(top.operators[0].name, "Shining");
+        assert!(calculator.combination(&["支援机械", "支援"]).is_none());
+    }
+}
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::Value;
+
+use crate::binding::tasks::{ClientType, Server};
 
 pub type ItemMap = HashMap<String, Item>;
 
@@ -15,3 +20,291 @@ pub struct Item {
     pub sort_id: i64,
     pub usage: Option<String>,
 }
+
+/// An operator of `battle_data.json`, with the names used by each client
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Character {
+    pub name: String,
+    pub name_en: Option<String>,
+    pub name_jp: Option<String>,
+    pub name_kr: Option<String>,
+    pub name_tw: Option<String>,
+    pub profession: String,
+    pub rarity: i64,
+}
+
+impl Character {
+    /// The name shown by `client`
+    pub fn name_for(&self, client: ClientType) -> Option<&str> {
+        match client {
+            ClientType::Official | ClientType::Bilibili => Some(&self.name),
+            ClientType::Twxy => self.name_tw.as_deref(),
+            ClientType::YoStarEN => self.name_en.as_deref(),
+            ClientType::YoStarJP => self.name_jp.as_deref(),
+            ClientType::YoStarKR => self.name_kr.as_deref(),
+        }
+    }
+
+    /// Whether `name` is the name of the operator in any client, ignoring case
+    pub fn is_named(&self, name: &str) -> bool {
+        [
+            Some(&self.name),
+            self.name_en.as_ref(),
+            self.name_jp.as_ref(),
+            self.name_kr.as_ref(),
+            self.name_tw.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|n| n.to_lowercase() == name.to_lowercase())
+    }
+}
+
+/// Operators keyed by id, such as `char_002_amiya`
+pub type CharacterMap = HashMap<String, Character>;
+
+pub fn load_characters<P: AsRef<Path>>(resource_dir: P) -> Result<CharacterMap> {
+    #[derive(Deserialize)]
+    struct BattleData {
+        chars: CharacterMap,
+    }
+
+    let path = resource_dir.as_ref().join("battle_data.json");
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let data: BattleData = serde_json::from_str(&data)?;
+    Ok(data.chars)
+}
+
+/// The `[pattern, replacement]` pairs of every `ocrReplace` in `tasks.json` or `tasks/`
+pub fn load_ocr_replaces<P: AsRef<Path>>(resource_dir: P) -> Result<Vec<(String, String)>> {
+    fn read(path: &Path, replaces: &mut Vec<(String, String)>) -> Result<()> {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                read(&entry?.path(), replaces)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "json") {
+            let tasks: HashMap<String, Value> =
+                serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            for task in tasks.values() {
+                let Some(pairs) = task["ocrReplace"].as_array() else {
+                    continue;
+                };
+                for pair in pairs {
+                    if let (Some(pattern), Some(replacement)) = (pair[0].as_str(), pair[1].as_str())
+                    {
+                        replaces.push((pattern.to_string(), replacement.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let resource_dir = resource_dir.as_ref();
+    let mut replaces = Vec::new();
+    for path in [resource_dir.join("tasks.json"), resource_dir.join("tasks")] {
+        if path.exists() {
+            read(&path, &mut replaces)?;
+        }
+    }
+    Ok(replaces)
+}
+
+/// The directory under `resource/global` holding the resources of `server`, if it is not CN
+pub fn global_resource_dir(server: Server) -> Option<&'static str> {
+    match server {
+        Server::CN => None,
+        Server::US => Some("YoStarEN"),
+        Server::JP => Some("YoStarJP"),
+        Server::KR => Some("YoStarKR"),
+    }
+}
+
+pub fn load_item_map<P: AsRef<Path>>(resource_dir: P) -> Result<ItemMap> {
+    let path = resource_dir.as_ref().join("item_index.json");
+    if !path.is_file() {
+        return Err(anyhow!("Item index not found: {}", path.display()));
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Name lookup for the items of an `ItemMap`
+pub trait ItemLookup {
+    /// The item whose name is exactly `name`, an error if several items share the name
+    fn find_by_name(&self, name: &str) -> Result<Option<(&str, &Item)>>;
+
+    /// Items whose name contains `query`, ignoring case and spaces, in game order
+    fn search(&self, query: &str) -> Vec<(&str, &Item)>;
+
+    /// Resolve an item id, an exact name, or a query matching a single item to an item id
+    fn resolve(&self, id_or_name: &str) -> Result<&str>;
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl ItemLookup for ItemMap {
+    fn find_by_name(&self, name: &str) -> Result<Option<(&str, &Item)>> {
+        let mut found = self
+            .iter()
+            .filter(|(_, item)| item.name == name)
+            .map(|(id, item)| (id.as_str(), item))
+            .collect::<Vec<_>>();
+        match found.len() {
+            0 | 1 => Ok(found.pop()),
+            _ => {
+                let mut ids = found.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+                ids.sort();
+                Err(anyhow!(
+                    "Ambiguous item {name}, shared by {}",
+                    ids.join(", ")
+                ))
+            }
+        }
+    }
+
+    fn search(&self, query: &str) -> Vec<(&str, &Item)> {
+        let query = normalize(query);
+        let mut found = self
+            .iter()
+            .filter(|(_, item)| normalize(&item.name).contains(&query))
+            .map(|(id, item)| (id.as_str(), item))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(id, item)| (item.sort_id, *id));
+        found
+    }
+
+    fn resolve(&self, id_or_name: &str) -> Result<&str> {
+        if let Some((id, _)) = self.get_key_value(id_or_name) {
+            return Ok(id);
+        }
+        if let Some((id, _)) = self.find_by_name(id_or_name)? {
+            return Ok(id);
+        }
+        match self.search(id_or_name).as_slice() {
+            [] => Err(anyhow!("Unknown item: {id_or_name}")),
+            [(id, _)] => Ok(id),
+            found => Err(anyhow!(
+                "Ambiguous item {id_or_name}, could be {}",
+                found
+                    .iter()
+                    .map(|(_, item)| item.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// Names of the pipeline tasks defined by `tasks.json`, or by every file under `tasks/`
+/// in newer resources
+pub fn load_task_names<P: AsRef<Path>>(resource_dir: P) -> Result<HashSet<String>> {
+    fn read(path: &Path, names: &mut HashSet<String>) -> Result<()> {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                read(&entry?.path(), names)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "json") {
+            let tasks: HashMap<String, Value> =
+                serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            names.extend(tasks.into_keys());
+        }
+        Ok(())
+    }
+
+    let resource_dir = resource_dir.as_ref();
+    let mut names = HashSet::new();
+    for path in [resource_dir.join("tasks.json"), resource_dir.join("tasks")] {
+        if path.exists() {
+            read(&path, &mut names)?;
+        }
+    }
+    Ok(names)
+}
+
+/// Whether `name` is one of `names`, or derived from one of them by `Prefix@` prefixes
+pub(crate) fn has_task_name(names: &HashSet<String>, name: &str) -> bool {
+    let mut name = name;
+    loop {
+        if names.contains(name) {
+            return true;
+        }
+        match name.split_once('@') {
+            Some((_, base)) => name = base,
+            None => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_item_lookup() {
+        let item = |name: &str, sort_id| Item {
+            name: name.to_string(),
+            sort_id,
+            ..Default::default()
+        };
+        let items = ItemMap::from([
+            ("30012".to_string(), item("Orirock Cube", 2)),
+            ("30013".to_string(), item("Orirock Cluster", 3)),
+            ("30011".to_string(), item("Orirock", 1)),
+        ]);
+        assert_eq!(items.resolve("30012").unwrap(), "30012");
+        assert_eq!(items.resolve("Orirock").unwrap(), "30011");
+        assert_eq!(items.resolve("orirock cube").unwrap(), "30012");
+        assert_eq!(items.search("rock").len(), 3);
+        assert_eq!(items.search("rock")[0].0, "30011");
+        assert!(items.resolve("rock").is_err());
+        assert!(items.resolve("Device").is_err());
+        assert_eq!(items.find_by_name("Orirock").unwrap().unwrap().0, "30011");
+        assert!(items.find_by_name("rock").unwrap().is_none());
+
+        let mut duplicated = items.clone();
+        duplicated.insert("30014".to_string(), item("Orirock", 4));
+        assert!(duplicated.find_by_name("Orirock").is_err());
+        assert!(duplicated.resolve("Orirock").is_err());
+        assert_eq!(duplicated.resolve("30014").unwrap(), "30014");
+    }
+
+    #[test]
+    fn test_task_names() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("tasks.json"),
+            r#"{ "Award": {}, "Reclamation@ExitLevel": {} }"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("tasks").join("Roguelike")).unwrap();
+        std::fs::write(
+            dir.path().join("tasks").join("Roguelike").join("Sami.json"),
+            r#"{ "Sami@Roguelike@Begin": {}, "StageTraderInvest": {} }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("tasks").join("README.md"), "not tasks").unwrap();
+
+        let names = load_task_names(dir.path()).unwrap();
+        assert_eq!(names.len(), 4);
+        assert!(names.contains("Sami@Roguelike@Begin"));
+
+        assert!(has_task_name(&names, "Award"));
+        assert!(has_task_name(&names, "Reclamation@ExitLevel"));
+        assert!(has_task_name(&names, "Mizuki@StageTraderInvest"));
+        assert!(has_task_name(&names, "Sarkaz@Mizuki@StageTraderInvest"));
+        assert!(!has_task_name(&names, "Award@Reclamation"));
+        assert!(!has_task_name(&names, "Fire@ExitLevel"));
+        assert!(!has_task_name(&names, "Awards"));
+        assert!(!has_task_name(&names, ""));
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(load_task_names(empty.path()).unwrap().is_empty());
+    }
+}
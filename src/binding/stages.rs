@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::tasks::Server;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StageKind {
+    /// 主线及别传
+    Main,
+    /// 资源收集，如 `CE-6`、`LS-6`
+    Resource,
+    /// 芯片搜索，如 `PR-A-1`
+    Chip,
+    /// 剿灭作战
+    Annihilation,
+    /// 活动关卡
+    Event,
+}
+
+/// Availability of a stage on one server, times are unix milliseconds
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Existence {
+    pub exist: bool,
+    pub open_time: Option<i64>,
+    pub close_time: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stage {
+    pub code: String,
+    #[serde(default)]
+    pub stage_id: String,
+    #[serde(default)]
+    pub stage_type: String,
+    #[serde(default)]
+    pub ap_cost: i64,
+    /// Keyed by server, see `Server::as_ref`
+    #[serde(default)]
+    pub existence: HashMap<String, Existence>,
+}
+
+/// Resource stages and the days they are open on, 0 is Monday
+const DAILY_STAGES: [(&str, usize, [bool; 7]); 9] = [
+    ("LS", 6, [true, true, true, true, true, true, true]),
+    ("CE", 6, [false, true, false, true, false, true, true]),
+    ("CA", 5, [false, true, true, false, true, false, true]),
+    ("SK", 5, [true, false, true, false, true, true, false]),
+    ("AP", 5, [true, false, false, true, false, true, true]),
+    ("PR-A", 2, [true, false, false, true, true, false, true]),
+    ("PR-B", 2, [true, true, false, false, true, true, false]),
+    ("PR-C", 2, [false, false, true, true, false, true, true]),
+    ("PR-D", 2, [false, true, true, false, false, true, true]),
+];
+
+const ANNIHILATION: &str = "Annihilation";
+
+impl Stage {
+    pub fn kind(&self) -> StageKind {
+        if self.code == ANNIHILATION {
+            StageKind::Annihilation
+        } else if self.code.starts_with("PR-") {
+            StageKind::Chip
+        } else if daily_days(&self.code).is_some() {
+            StageKind::Resource
+        } else if self.stage_type == "ACTIVITY" {
+            StageKind::Event
+        } else {
+            StageKind::Main
+        }
+    }
+
+    /// Whether the stage exists on `server`, a stage without existence info exists everywhere
+    pub fn exists_on(&self, server: Server) -> bool {
+        self.existence.is_empty() || self.existence.get(server.as_ref()).is_some_and(|e| e.exist)
+    }
+
+    pub fn is_open_at(&self, server: Server, at: SystemTime) -> bool {
+        if !self.exists_on(server) {
+            return false;
+        }
+        if let Some(days) = daily_days(&self.code) {
+            return days[server_weekday(server, at)];
+        }
+        let Some(existence) = self.existence.get(server.as_ref()) else {
+            return true;
+        };
+        let now = at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let opened = existence.open_time.unwrap_or(i64::MIN) <= now;
+        let closed = existence.close_time.is_some_and(|t| t <= now);
+        opened && !closed
+    }
+}
+
+fn daily_days(code: &str) -> Option<&'static [bool; 7]> {
+    let (prefix, _) = code.rsplit_once('-')?;
+    DAILY_STAGES
+        .iter()
+        .find(|(p, _, _)| *p == prefix)
+        .map(|(_, _, days)| days)
+}
+
+/// Hours from UTC to the server's daily reset at 04:00 local time
+fn reset_offset_hours(server: Server) -> i64 {
+    match server {
+        Server::CN => 4,
+        Server::JP | Server::KR => 5,
+        Server::US => -11,
+    }
+}
+
+/// The game day of the week on `server`, 0 is Monday
+pub fn server_weekday(server: Server, at: SystemTime) -> usize {
+    let secs = match at.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let days = (secs + reset_offset_hours(server) * 3600).div_euclid(86400);
+    // 1970-01-01 is a Thursday
+    (days + 3).rem_euclid(7) as usize
+}
+
+/// Strip the `Normal`/`Hard` difficulty suffix accepted by MaaCore
+fn base_code(code: &str) -> &str {
+    for suffix in ["Normal", "Hard", "NORMAL", "HARD"] {
+        if let Some(base) = code.strip_suffix(suffix) {
+            return base.trim_end_matches('-');
+        }
+    }
+    code
+}
+
+/// Stages that `Fight` accepts, from `resource/stages.json` plus the resource stages
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct StageCatalog {
+    stages: HashMap<String, Stage>,
+}
+
+impl StageCatalog {
+    pub fn from_stages(stages: Vec<Stage>) -> Self {
+        let mut catalog = StageCatalog {
+            stages: stages.into_iter().map(|s| (s.code.clone(), s)).collect(),
+        };
+        let builtin = DAILY_STAGES
+            .iter()
+            .flat_map(|(prefix, max, _)| (1..=*max).map(move |i| format!("{prefix}-{i}")))
+            .chain([ANNIHILATION.to_string()]);
+        for code in builtin {
+            catalog.stages.entry(code.clone()).or_insert(Stage {
+                code,
+                stage_id: String::new(),
+                stage_type: "DAILY".to_string(),
+                ap_cost: 0,
+                existence: HashMap::new(),
+            });
+        }
+        catalog
+    }
+
+    pub fn load<P: AsRef<Path>>(resource_dir: P) -> Result<Self> {
+        let path = resource_dir.as_ref().join("stages.json");
+        let stages = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        Ok(Self::from_stages(serde_json::from_str(&stages)?))
+    }
+
+    /// Later stages replace the ones with the same code, as for incremental resources
+    pub fn extend(&mut self, other: StageCatalog) {
+        self.stages.extend(other.stages);
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Stage> {
+        self.stages.get(base_code(code))
+    }
+
+    pub fn stages(&self) -> impl Iterator<Item = &Stage> {
+        self.stages.values()
+    }
+
+    /// Check that `Fight` can go to `code` on `server`, an empty code means the current stage
+    pub fn validate(&self, code: &str, server: Server) -> Result<()> {
+        if code.is_empty() {
+            return Ok(());
+        }
+        let stage = self
+            .get(code)
+            .ok_or_else(|| anyhow!("Unknown stage: {code}"))?;
+        if !stage.exists_on(server) {
+            return Err(anyhow!(
+                "Stage {code} does not exist on {}",
+                server.as_ref()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_open_at(&self, code: &str, server: Server, at: SystemTime) -> bool {
+        self.get(code).is_some_and(|s| s.is_open_at(server, at))
+    }
+
+    /// Whether `code` can be fought today, by the server's weekly rotation and event times
+    pub fn is_open_today(&self, code: &str, server: Server) -> bool {
+        self.is_open_at(code, server, SystemTime::now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_stage_catalog() {
+        let stages: Vec<Stage> = serde_json::from_str(
+            r#"[
+                {"stageType": "MAIN", "stageId": "main_01-07", "code": "1-7", "apCost": 6,
+                 "existence": {"CN": {"exist": true}, "US": {"exist": true}}},
+                {"stageType": "ACTIVITY", "stageId": "act_01", "code": "EV-1", "apCost": 9,
+                 "existence": {"CN": {"exist": true, "openTime": 1000000, "closeTime": 2000000}}}
+            ]"#,
+        )
+        .unwrap();
+        let catalog = StageCatalog::from_stages(stages);
+
+        assert!(catalog.validate("", Server::CN).is_ok());
+        assert!(catalog.validate("1-7", Server::US).is_ok());
+        assert!(catalog.validate("1-7-Hard", Server::CN).is_ok());
+        assert!(catalog.validate("1-7", Server::JP).is_err());
+        assert!(catalog.validate("CE-6", Server::JP).is_ok());
+        assert!(catalog.validate("CE-7", Server::CN).is_err());
+        assert_eq!(catalog.get("PR-A-2").unwrap().kind(), StageKind::Chip);
+        assert_eq!(catalog.get("EV-1").unwrap().kind(), StageKind::Event);
+
+        // 2023-06-05 04:00 in CN starts Monday, an hour earlier is still Sunday
+        let monday = UNIX_EPOCH + Duration::from_secs(1685908800);
+        let cn_3am = monday - Duration::from_secs(3600);
+        assert_eq!(server_weekday(Server::CN, monday), 0);
+        assert_eq!(server_weekday(Server::CN, cn_3am), 6);
+        assert!(catalog.is_open_at("CE-6", Server::CN, cn_3am));
+        assert!(!catalog.is_open_at("CE-6", Server::CN, monday));
+        assert!(catalog.is_open_at("LS-6", Server::CN, monday));
+
+        let during = UNIX_EPOCH + Duration::from_millis(1500000);
+        assert!(catalog.is_open_at("EV-1", Server::CN, during));
+        assert!(!catalog.is_open_at("EV-1", Server::CN, monday));
+    }
+}
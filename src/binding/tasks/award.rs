@@ -1,18 +1,35 @@
+use std::collections::BTreeSet;
 use std::marker::PhantomData;
 
+use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::binding::tasks::{Paused, Running, State, StoppedTask};
+use crate::binding::events::{SubTaskExtraInfo, SubTaskStart};
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+use crate::history::{History, Stamped};
 
 /// 领取日常奖励
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct Award<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
     #[serde(skip)]
     id: Option<usize>,
+
+    award: bool,
+    mail: bool,
+    recruit: bool,
+    orundum: bool,
+    mining: bool,
+    specialaccess: bool,
+}
+
+impl<T: State> Default for Award<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: State> Award<T> {
@@ -20,8 +37,50 @@ impl<T: State> Award<T> {
         Award {
             _phantom: PhantomData,
             id: None,
+            award: true,
+            mail: false,
+            recruit: false,
+            orundum: false,
+            mining: false,
+            specialaccess: false,
         }
     }
+
+    /// 领取每日/每周任务奖励，可选，默认 true
+    pub fn award(mut self, award: bool) -> Self {
+        self.award = award;
+        self
+    }
+
+    /// 领取所有邮件奖励，可选，默认 false
+    pub fn mail(mut self, mail: bool) -> Self {
+        self.mail = mail;
+        self
+    }
+
+    /// 进行限定池每日一次的免费单抽，可选，默认 false
+    pub fn recruit(mut self, recruit: bool) -> Self {
+        self.recruit = recruit;
+        self
+    }
+
+    /// 领取幸运墙的合成玉奖励，可选，默认 false
+    pub fn orundum(mut self, orundum: bool) -> Self {
+        self.orundum = orundum;
+        self
+    }
+
+    /// 领取限时开采许可的合成玉奖励，可选，默认 false
+    pub fn mining(mut self, mining: bool) -> Self {
+        self.mining = mining;
+        self
+    }
+
+    /// 领取五周年赠送的月卡奖励，可选，默认 false
+    pub fn specialaccess(mut self, specialaccess: bool) -> Self {
+        self.specialaccess = specialaccess;
+        self
+    }
 }
 
 impl Award<Paused> {
@@ -33,6 +92,12 @@ impl Award<Paused> {
         Award {
             _phantom: PhantomData,
             id: self.id,
+            award: self.award,
+            mail: self.mail,
+            recruit: self.recruit,
+            orundum: self.orundum,
+            mining: self.mining,
+            specialaccess: self.specialaccess,
         }
     }
 }
@@ -46,3 +111,111 @@ impl<'a> StoppedTask<'a> for Award<Paused> {
         "Award"
     }
 }
+
+/// 奖励的种类，对应 `Award` 的各选项
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AwardKind {
+    Missions,
+    Mail,
+    FreeGacha,
+    Orundum,
+    Mining,
+    SpecialAccess,
+}
+
+impl AwardKind {
+    /// The kind a sub task of the award pipeline tries to collect, by its name
+    fn of_task(task: &str) -> Option<Self> {
+        // Tasks may be prefixed, e.g. `Award@ReceiveAward`
+        let task = task.rsplit('@').next().unwrap_or(task);
+        if task.contains("SpecialAccess") {
+            Some(AwardKind::SpecialAccess)
+        } else if task.contains("Mining") {
+            Some(AwardKind::Mining)
+        } else if task.contains("Orundum") {
+            Some(AwardKind::Orundum)
+        } else if task.contains("Gacha") || task.contains("Recruit") {
+            Some(AwardKind::FreeGacha)
+        } else if task.contains("Mail") {
+            Some(AwardKind::Mail)
+        } else if task.contains("Award") || task.contains("Daily") || task.contains("Weekly") {
+            Some(AwardKind::Missions)
+        } else {
+            None
+        }
+    }
+}
+
+/// 尝试领取的奖励，取自奖励相关的子任务名。
+///
+/// 子任务开始即记为尝试，活动未开放或已领取时也会开始，不代表确实领到了奖励
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AwardReport {
+    #[serde(alias = "collected")]
+    pub attempted: BTreeSet<AwardKind>,
+}
+
+impl AwardReport {
+    const HISTORY_NAME: &'static str = "award";
+
+    pub fn attempted(&self, kind: AwardKind) -> bool {
+        self.attempted.contains(&kind)
+    }
+
+    pub fn save(&self, history: &History) -> Result<()> {
+        history.append(Self::HISTORY_NAME, &Stamped::now(self))
+    }
+
+    pub fn records(history: &History) -> Result<Vec<Stamped<AwardReport>>> {
+        history.records(Self::HISTORY_NAME)
+    }
+}
+
+impl TaskReport for AwardReport {
+    /// The award task reports no extra info
+    fn update(&mut self, _info: &SubTaskExtraInfo) {}
+
+    fn sub_task_start(&mut self, info: &SubTaskStart) {
+        if let Some(kind) = info.details.task.as_deref().and_then(AwardKind::of_task) {
+            self.attempted.insert(kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = AwardReport::default();
+        for task in [
+            "AwardBegin@ReceiveAward",
+            "MailReceiveAll",
+            "OrundumActivityBegin",
+            "StartUp",
+        ] {
+            let info: SubTaskStart = serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Award", "taskid": 1,
+                "uuid": "", "details": { "task": task }
+            }))
+            .unwrap();
+            report.sub_task_start(&info);
+        }
+        assert_eq!(
+            report.attempted,
+            BTreeSet::from([AwardKind::Missions, AwardKind::Mail, AwardKind::Orundum])
+        );
+        assert!(!report.attempted(AwardKind::Mining));
+        let old: AwardReport = serde_json::from_str(r#"{"collected": ["mail"]}"#).unwrap();
+        assert!(old.attempted(AwardKind::Mail));
+        assert_eq!(
+            serde_json::to_value(Award::<Paused>::new().mail(true)).unwrap(),
+            serde_json::json!({
+                "award": true, "mail": true, "recruit": false,
+                "orundum": false, "mining": false, "specialaccess": false
+            })
+        );
+    }
+}
@@ -6,8 +6,8 @@ use serde::Serialize;
 use crate::binding::tasks::{Paused, Running, State, StoppedTask};
 
 /// 开始唤醒
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct CloseDown<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -15,6 +15,12 @@ pub struct CloseDown<T: State> {
     id: Option<usize>,
 }
 
+impl<T: State> Default for CloseDown<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: State> CloseDown<T> {
     pub fn new() -> Self {
         CloseDown {
@@ -0,0 +1,552 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::SubTaskExtraInfo;
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+
+/// 干员练度要求
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OperRequirements {
+    pub elite: u8,
+    pub level: u8,
+    pub skill_level: u8,
+    pub module: i8,
+    pub potentiality: u8,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotOper {
+    /// 干员名
+    pub name: String,
+    /// 技能序号，1 ~ 3，默认 1
+    #[serde(default = "default_skill")]
+    pub skill: u8,
+    /// 技能用法，0 ~ 3，默认 0
+    #[serde(default)]
+    pub skill_usage: u8,
+    #[serde(default)]
+    pub requirements: OperRequirements,
+}
+
+fn default_skill() -> u8 {
+    1
+}
+
+/// 干员群组，作业中可用群组名代替干员名，执行时会从中选择一个已有的干员
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotGroup {
+    pub name: String,
+    pub opers: Vec<CopilotOper>,
+}
+
+/// 动作类型，与 MaaCore 相同，英文名不区分大小写，也可用中文名
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ActionType {
+    #[default]
+    Deploy,
+    Skill,
+    Retreat,
+    SpeedUp,
+    BulletTime,
+    SkillUsage,
+    Output,
+    SkillDaemon,
+    MoveCamera,
+}
+
+impl ActionType {
+    pub const ALL: [ActionType; 9] = [
+        ActionType::Deploy,
+        ActionType::Skill,
+        ActionType::Retreat,
+        ActionType::SpeedUp,
+        ActionType::BulletTime,
+        ActionType::SkillUsage,
+        ActionType::Output,
+        ActionType::SkillDaemon,
+        ActionType::MoveCamera,
+    ];
+
+    /// The names MaaCore accepts, matched ignoring case
+    pub fn names(&self) -> &'static [&'static str] {
+        match self {
+            ActionType::Deploy => &["Deploy", "部署"],
+            ActionType::Skill => &["Skill", "技能"],
+            ActionType::Retreat => &["Retreat", "撤退"],
+            ActionType::SpeedUp => &["SpeedUp", "二倍速"],
+            ActionType::BulletTime => &["BulletTime", "子弹时间"],
+            ActionType::SkillUsage => &["SkillUsage", "技能用法"],
+            ActionType::Output => &["Output", "输出", "打印"],
+            ActionType::SkillDaemon => &["SkillDaemon", "DoNothing", "摆完挂机", "开摆"],
+            ActionType::MoveCamera => &["MoveCamera", "移动镜头"],
+        }
+    }
+}
+
+impl TryFrom<&str> for ActionType {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        ActionType::ALL
+            .into_iter()
+            .find(|t| t.names().iter().any(|n| n.eq_ignore_ascii_case(s)))
+            .ok_or_else(|| anyhow!("Unknown copilot action type: {s}"))
+    }
+}
+
+impl TryFrom<String> for ActionType {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        ActionType::try_from(s.as_str())
+    }
+}
+
+/// 部署方向，英文名不区分大小写，也可用中文名
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+    None,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 5] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+        Direction::None,
+    ];
+
+    /// The names MaaCore accepts, matched ignoring case
+    pub fn names(&self) -> [&'static str; 2] {
+        match self {
+            Direction::Left => ["Left", "左"],
+            Direction::Right => ["Right", "右"],
+            Direction::Up => ["Up", "上"],
+            Direction::Down => ["Down", "下"],
+            Direction::None => ["None", "无"],
+        }
+    }
+}
+
+impl TryFrom<&str> for Direction {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Direction::ALL
+            .into_iter()
+            .find(|d| d.names().iter().any(|n| n.eq_ignore_ascii_case(s)))
+            .ok_or_else(|| anyhow!("Unknown direction: {s}"))
+    }
+}
+
+impl TryFrom<String> for Direction {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Direction::try_from(s.as_str())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotAction {
+    #[serde(rename = "type", default)]
+    pub type_: ActionType,
+    /// 击杀数条件
+    pub kills: Option<i64>,
+    /// 费用条件
+    pub costs: Option<i64>,
+    /// 费用变化量条件
+    pub cost_changes: Option<i64>,
+    /// 冷却中干员数条件
+    pub cooling: Option<i64>,
+    /// 干员名或群组名
+    #[serde(default)]
+    pub name: String,
+    pub location: Option<[i64; 2]>,
+    pub direction: Option<Direction>,
+    pub skill_usage: Option<u8>,
+    pub skill_times: Option<i64>,
+    pub pre_delay: Option<i64>,
+    pub post_delay: Option<i64>,
+    /// 移动镜头的距离
+    pub distance: Option<[f64; 2]>,
+    pub doc: Option<String>,
+    pub doc_color: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotDoc {
+    pub title: Option<String>,
+    pub title_color: Option<String>,
+    pub details: Option<String>,
+    pub details_color: Option<String>,
+}
+
+/// 自动抄作业的作业文件
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotJob {
+    /// 关卡名，如 `1-7`、`obt/main/level_main_01-07`
+    pub stage_name: String,
+    #[serde(default)]
+    pub opers: Vec<CopilotOper>,
+    #[serde(default)]
+    pub groups: Vec<CopilotGroup>,
+    pub actions: Vec<CopilotAction>,
+    pub minimum_required: Option<String>,
+    pub doc: Option<CopilotDoc>,
+}
+
+/// Check the operators and actions shared by every kind of copilot job, `others` are names
+/// that can be deployed besides `opers` and `groups`
+pub(crate) fn validate_actions(
+    opers: &[CopilotOper],
+    groups: &[CopilotGroup],
+    others: &[&str],
+    actions: &[CopilotAction],
+) -> Result<()> {
+    let mut names = HashSet::new();
+    for oper in opers
+        .iter()
+        .chain(groups.iter().flat_map(|g| g.opers.iter()))
+    {
+        if oper.name.is_empty() {
+            return Err(anyhow!("Operator without name"));
+        }
+        if !(1..=3).contains(&oper.skill) {
+            return Err(anyhow!("Invalid skill {} of {}", oper.skill, oper.name));
+        }
+        if oper.skill_usage > 3 {
+            return Err(anyhow!(
+                "Invalid skill usage {} of {}",
+                oper.skill_usage,
+                oper.name
+            ));
+        }
+    }
+    for name in opers
+        .iter()
+        .map(|o| &o.name)
+        .chain(groups.iter().map(|g| &g.name))
+    {
+        if !names.insert(name.as_str()) {
+            return Err(anyhow!("Duplicated operator or group: {name}"));
+        }
+    }
+    names.extend(others);
+
+    for (i, action) in actions.iter().enumerate() {
+        let known = names.contains(action.name.as_str());
+        match action.type_ {
+            ActionType::Deploy if !known => {
+                return Err(anyhow!(
+                    "Action {i} deploys unknown operator: {:?}",
+                    action.name
+                ));
+            }
+            ActionType::Deploy if action.location.is_none() => {
+                return Err(anyhow!(
+                    "Action {i} deploys {} without location",
+                    action.name
+                ));
+            }
+            ActionType::Skill | ActionType::Retreat | ActionType::SkillUsage
+                if !known && action.location.is_none() =>
+            {
+                return Err(anyhow!(
+                    "Action {i} targets neither a known operator nor a location"
+                ));
+            }
+            ActionType::MoveCamera if action.distance.is_none() => {
+                return Err(anyhow!("Action {i} moves the camera without distance"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl CopilotJob {
+    /// Read and validate a job file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let job = std::fs::read_to_string(path)?;
+        let job: CopilotJob = serde_json::from_str(&job)
+            .map_err(|e| anyhow!("Invalid copilot file {}: {e}", path.display()))?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.stage_name.is_empty() {
+            return Err(anyhow!("Copilot job without stage name"));
+        }
+        if self.actions.is_empty() {
+            return Err(anyhow!("Copilot job without actions"));
+        }
+        validate_actions(&self.opers, &self.groups, &[], &self.actions)
+    }
+}
+
+/// 自动抄作业
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct Copilot<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+
+    filename: String,
+    formation: bool,
+}
+
+impl<T: State> Default for Copilot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> Copilot<T> {
+    pub fn new() -> Self {
+        Copilot {
+            _phantom: PhantomData,
+            id: None,
+            filename: String::new(),
+            formation: false,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl Copilot<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    /// 作业 JSON 的文件路径，绝对、相对路径均可。不支持运行中设置
+    pub fn filename<P: AsRef<Path>>(mut self, filename: P) -> Self {
+        self.filename = filename.as_ref().to_string_lossy().to_string();
+        self
+    }
+
+    /// 是否进行 “快捷编队”，可选，默认否。不支持运行中设置
+    pub fn formation(mut self, formation: bool) -> Self {
+        self.formation = formation;
+        self
+    }
+
+    pub fn start(self) -> Copilot<Running> {
+        Copilot {
+            _phantom: PhantomData,
+            id: self.id,
+            filename: self.filename,
+            formation: self.formation,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for Copilot<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "Copilot"
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.filename.is_empty() {
+            return Err(anyhow!("Copilot task without filename"));
+        }
+        Ok(())
+    }
+
+    fn validate(&self, _maa: &MAAConnection) -> Result<()> {
+        self.check()?;
+        CopilotJob::from_file(&self.filename)?;
+        Ok(())
+    }
+}
+
+/// 自动抄作业过程中的事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CopilotEvent {
+    /// 不支持的关卡
+    UnsupportedLevel,
+    /// 快捷编队中要编入的干员
+    Formation(Vec<String>),
+    /// 快捷编队中选中了的干员
+    FormationSelected(String),
+    /// 当前执行的动作
+    Action {
+        action: String,
+        target: String,
+        doc: Option<String>,
+    },
+}
+
+impl CopilotEvent {
+    pub fn from_info(info: &SubTaskExtraInfo) -> Option<Self> {
+        let details = &info.details;
+        match info.what.as_str() {
+            "UnsupportedLevel" => Some(CopilotEvent::UnsupportedLevel),
+            "BattleFormation" => Some(CopilotEvent::Formation(details.formation.clone())),
+            "BattleFormationSelected" => Some(CopilotEvent::FormationSelected(
+                details.selected.clone().unwrap_or_default(),
+            )),
+            "CopilotAction" => Some(CopilotEvent::Action {
+                action: details.action.clone().unwrap_or_default(),
+                target: details.target.clone().unwrap_or_default(),
+                doc: details.doc.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// 自动抄作业的全部事件，按发生顺序记录
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopilotReport {
+    pub events: Vec<CopilotEvent>,
+}
+
+impl CopilotReport {
+    pub fn unsupported_level(&self) -> bool {
+        self.events.contains(&CopilotEvent::UnsupportedLevel)
+    }
+
+    /// 已执行的动作数
+    pub fn actions_done(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|e| matches!(e, CopilotEvent::Action { .. }))
+            .count()
+    }
+}
+
+impl TaskReport for CopilotReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        if let Some(event) = CopilotEvent::from_info(info) {
+            self.events.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_job() {
+        let job = r#"{
+            "stage_name": "1-7",
+            "opers": [{ "name": "史尔特尔", "skill": 3 }],
+            "groups": [{ "name": "重装", "opers": [{ "name": "塞雷娅" }, { "name": "星熊" }] }],
+            "actions": [
+                { "type": "部署", "name": "重装", "location": [5, 5], "direction": "左" },
+                { "type": "Deploy", "name": "史尔特尔", "location": [6, 4], "direction": "Right" },
+                { "type": "Skill", "name": "史尔特尔" },
+                { "type": "Retreat", "location": [5, 5] }
+            ]
+        }"#;
+        let job: CopilotJob = serde_json::from_str(job).unwrap();
+        assert!(job.validate().is_ok());
+
+        let mut typo = job.clone();
+        typo.actions[1].name = "史尔特".to_string();
+        assert!(typo.validate().is_err());
+
+        let mut no_location = job.clone();
+        no_location.actions[0].location = None;
+        assert!(no_location.validate().is_err());
+
+        let mut duplicated = job.clone();
+        duplicated.opers.push(CopilotOper {
+            name: "重装".to_string(),
+            ..Default::default()
+        });
+        assert!(duplicated.validate().is_err());
+
+        let mut skill = job.clone();
+        skill.opers[0].skill = 4;
+        assert!(skill.validate().is_err());
+
+        let mut camera = job.clone();
+        camera.actions.push(CopilotAction {
+            type_: ActionType::MoveCamera,
+            ..Default::default()
+        });
+        assert!(camera.validate().is_err());
+        camera.actions[4].distance = Some([4.5, 0.0]);
+        assert!(camera.validate().is_ok());
+
+        let mut empty = job;
+        empty.actions.clear();
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn test_action_names() {
+        let job: CopilotJob = serde_json::from_str(
+            r#"{
+                "stage_name": "1-7",
+                "opers": [{ "name": "史尔特尔" }],
+                "actions": [
+                    { "type": "deploy", "name": "史尔特尔", "location": [6, 4], "direction": "left" },
+                    { "type": "SKILL", "name": "史尔特尔" },
+                    { "type": "Speedup" },
+                    { "type": "bullettime" },
+                    { "type": "开摆" },
+                    { "type": "输出", "doc": "done" },
+                    { "name": "史尔特尔", "location": [1, 1], "direction": "DOWN" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(job.validate().is_ok());
+        let types = job.actions.iter().map(|a| a.type_).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                ActionType::Deploy,
+                ActionType::Skill,
+                ActionType::SpeedUp,
+                ActionType::BulletTime,
+                ActionType::SkillDaemon,
+                ActionType::Output,
+                ActionType::Deploy,
+            ]
+        );
+        assert_eq!(job.actions[0].direction, Some(Direction::Left));
+        assert_eq!(job.actions[6].direction, Some(Direction::Down));
+
+        // Written back with the names MaaCore documents
+        let json = serde_json::to_value(&job.actions[0]).unwrap();
+        assert_eq!(json["type"], "Deploy");
+        assert_eq!(json["direction"], "Left");
+        assert_eq!(
+            serde_json::from_value::<CopilotAction>(json).unwrap(),
+            job.actions[0]
+        );
+
+        assert!(ActionType::try_from("Jump").is_err());
+        assert!(serde_json::from_str::<Direction>(r#""north""#).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::tasks::{Paused, Running, State, StoppedTask};
+
+/// 自定义任务，依次执行资源中定义的任务
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct Custom<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+
+    task_names: Vec<String>,
+}
+
+impl<T: State> Default for Custom<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> Custom<T> {
+    pub fn new() -> Self {
+        Custom {
+            _phantom: PhantomData,
+            id: None,
+            task_names: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl Custom<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    /// 要执行的任务名，参考 `resource/tasks.json`。不支持运行中设置
+    pub fn task_names(mut self, task_names: Vec<&str>) -> Self {
+        self.task_names = task_names.into_iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn start(self) -> Custom<Running> {
+        Custom {
+            _phantom: PhantomData,
+            id: self.id,
+            task_names: self.task_names,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for Custom<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "Custom"
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.task_names.is_empty() {
+            return Err(anyhow!("Custom task without task names"));
+        }
+        Ok(())
+    }
+
+    fn validate(&self, maa: &MAAConnection) -> Result<()> {
+        self.check()?;
+        let unknown = self
+            .task_names
+            .iter()
+            .filter(|name| !maa.has_task(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Err(anyhow!("Unknown tasks: {}", unknown.join(", ")));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::binding::events::SubTaskExtraInfo;
+use crate::binding::resources::ItemMap;
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+use crate::history::History;
+
+/// 仓库识别
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct Depot<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+}
+
+impl<T: State> Default for Depot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> Depot<T> {
+    pub fn new() -> Self {
+        Depot {
+            _phantom: PhantomData,
+            id: None,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl Depot<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    pub fn start(self) -> Depot<Running> {
+        Depot {
+            _phantom: PhantomData,
+            id: self.id,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for Depot<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "Depot"
+    }
+}
+
+/// 仓库库存，物品 ID 到数量
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    pub items: BTreeMap<String, i64>,
+}
+
+impl Inventory {
+    const HISTORY_NAME: &'static str = "depot";
+
+    /// Keep only the items that exist in `item_map`
+    pub fn from_counts(counts: BTreeMap<String, i64>, item_map: &ItemMap) -> Self {
+        let items = counts
+            .into_iter()
+            .filter(|(id, _)| {
+                let known = item_map.contains_key(id);
+                if !known {
+                    warn!("Unknown item in depot: {}", id);
+                }
+                known
+            })
+            .collect();
+        Inventory { items }
+    }
+
+    pub fn get(&self, item_id: &str) -> i64 {
+        self.items.get(item_id).copied().unwrap_or(0)
+    }
+
+    /// ArkPlanner (penguin-stats planner) import format
+    pub fn to_arkplanner(&self, item_map: &ItemMap) -> Value {
+        let items = self
+            .items
+            .iter()
+            .map(|(id, have)| {
+                let name = item_map
+                    .get(id)
+                    .map(|i| i.name.as_str())
+                    .unwrap_or_default();
+                json!({ "id": id, "have": have, "name": name })
+            })
+            .collect::<Vec<_>>();
+        json!({ "@type": "@penguin-statistics/depot", "items": items })
+    }
+
+    /// lolicon / yituliu import format, a flat item id to count object
+    pub fn to_lolicon(&self) -> Value {
+        json!(self.items)
+    }
+
+    pub fn save(&self, history: &History) -> Result<()> {
+        history.save(Self::HISTORY_NAME, self)
+    }
+
+    pub fn load(history: &History) -> Result<Option<Self>> {
+        history.load(Self::HISTORY_NAME)
+    }
+}
+
+/// 仓库识别结果，识别完成前 `inventory` 为已识别的部分
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepotReport {
+    pub done: bool,
+    pub inventory: Inventory,
+}
+
+impl DepotReport {
+    /// The recognized items that exist in `item_map`
+    pub fn inventory(&self, item_map: &ItemMap) -> Inventory {
+        Inventory::from_counts(self.inventory.items.clone(), item_map)
+    }
+}
+
+impl TaskReport for DepotReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        if info.what != "DepotInfo" {
+            return;
+        }
+        let Some(lolicon) = &info.details.lolicon else {
+            return;
+        };
+        match serde_json::from_value(lolicon["object"].clone()) {
+            Ok(items) => {
+                self.inventory = Inventory { items };
+                self.done = info.details.done.unwrap_or(false);
+            }
+            Err(e) => warn!("Invalid depot info: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::resources::Item;
+
+    #[test]
+    fn test_depot_export() {
+        let info: SubTaskExtraInfo = serde_json::from_value(json!({
+            "class": "asst::DepotTask", "subtask": "", "taskchain": "Depot",
+            "taskid": 1, "uuid": "", "what": "DepotInfo",
+            "details": {
+                "done": true,
+                "lolicon": { "object": { "30012": 120, "4001": 3000000, "99999": 1 }, "data": "" }
+            }
+        }))
+        .unwrap();
+        let mut report = DepotReport::default();
+        report.update(&info);
+        assert!(report.done);
+
+        let item_map = ItemMap::from([
+            (
+                "30012".to_string(),
+                Item {
+                    name: "固源岩".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "4001".to_string(),
+                Item {
+                    name: "龙门币".to_string(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let inventory = report.inventory(&item_map);
+        assert_eq!(inventory.get("30012"), 120);
+        assert_eq!(inventory.get("99999"), 0);
+        assert_eq!(
+            inventory.to_lolicon(),
+            json!({ "30012": 120, "4001": 3000000 })
+        );
+        assert_eq!(
+            inventory.to_arkplanner(&item_map)["items"][0],
+            json!({ "id": "30012", "have": 120, "name": "固源岩" })
+        );
+    }
+}
@@ -1,9 +1,14 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
+use anyhow::{anyhow, Result};
+use log::warn;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::binding::connection::MAAConnection;
+use crate::binding::resources::{ItemLookup, ItemMap};
+use crate::binding::stages::StageCatalog;
 use crate::binding::tasks::{ClientType, Paused, Running, Server, State, StoppedTask};
 
 fn is_zero(v: &usize) -> bool {
@@ -11,8 +16,8 @@ fn is_zero(v: &usize) -> bool {
 }
 
 /// 刷理智
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct Fight<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -46,6 +51,12 @@ pub struct Fight<T: State> {
     dr_grandet: bool,
 }
 
+impl<T: State> Default for Fight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: State> Fight<T> {
     pub fn new() -> Self {
         Fight {
@@ -110,6 +121,23 @@ impl<T: State> Fight<T> {
         self
     }
 
+    /// 同 `stop_when_drop`，但 Key 可为物品 ID 或物品名，按 `items` 解析。
+    ///
+    /// 物品名可只写一部分，只要能唯一确定物品即可，如
+    /// `maa.items(Server::US)` 中的 `"orirock cube"`
+    pub fn stop_when_drop_items(
+        mut self,
+        drop: HashMap<&str, usize>,
+        items: &ItemMap,
+    ) -> Result<Self> {
+        let mut resolved = HashMap::new();
+        for (item, count) in drop {
+            resolved.insert(items.resolve(item)?.to_string(), count);
+        }
+        self.drop = resolved;
+        Ok(self)
+    }
+
     /// 上报到企鹅物流，设定设定企鹅物流 ID，可选，默认 false
     pub fn report_to_penguin(mut self, penguin_id: String) -> Self {
         self.report_to_penguin = true;
@@ -144,12 +172,23 @@ impl Fight<Paused> {
     ///
     /// 可在关卡结尾输入`Normal/Hard`表示需要切换标准与磨难难度
     ///
-    /// 另支持少部分资源关卡，如 `CE-6` 等 (请参考 C# 集成示例)
+    /// 另支持资源关卡，如 `CE-6`、`PR-A-1`，及剿灭 `Annihilation`，见 `StageCatalog`
     pub fn stage(mut self, stage: &str) -> Self {
         self.stage = stage.to_string();
         self
     }
 
+    /// The stage set by `stage`, empty for the current stage
+    pub fn stage_code(&self) -> &str {
+        &self.stage
+    }
+
+    /// 同 `stage`，但关卡须存在于 `stages` 中，且在 `server` 上开放过；同时设定服务器为 `server`，同 `server`
+    pub fn try_stage(self, stage: &str, server: Server, stages: &StageCatalog) -> Result<Self> {
+        stages.validate(stage, server)?;
+        Ok(self.stage(stage).server(server))
+    }
+
     pub fn new_paused() -> Self {
         Self::new()
     }
@@ -181,4 +220,58 @@ impl<'a> StoppedTask<'a> for Fight<Paused> {
     fn name(&self) -> &'static str {
         "Fight"
     }
+
+    fn check(&self) -> Result<()> {
+        Server::try_from(self.server.as_str())?;
+        if !self.client_type.is_empty() {
+            ClientType::try_from(self.client_type.as_str())?;
+        }
+        if let Some((item, _)) = self.drop.iter().find(|(_, count)| **count == 0) {
+            return Err(anyhow!("Drop count of {item} must be positive"));
+        }
+        Ok(())
+    }
+
+    fn validate(&self, maa: &MAAConnection) -> Result<()> {
+        self.check()?;
+        let server = Server::try_from(self.server.as_str())?;
+        if self.stage.is_empty() {
+            return Ok(());
+        }
+        let stages = maa.stages()?;
+        stages.validate(&self.stage, server)?;
+        if !stages.is_open_today(&self.stage, server) {
+            warn!("Stage {} is not open today", self.stage);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::stages::Stage;
+
+    #[test]
+    fn test_try_stage() {
+        let stages: Vec<Stage> = serde_json::from_str(
+            r#"[{"stageType": "ACTIVITY", "stageId": "act_01", "code": "EV-1", "apCost": 9,
+                 "existence": {"US": {"exist": true}}}]"#,
+        )
+        .unwrap();
+        let stages = StageCatalog::from_stages(stages);
+
+        let fight = Fight::new_paused()
+            .try_stage("EV-1", Server::US, &stages)
+            .unwrap();
+        assert_eq!(fight.stage_code(), "EV-1");
+        assert_eq!(fight.server, "US");
+        assert!(Fight::new_paused()
+            .server(Server::US)
+            .try_stage("EV-1", Server::CN, &stages)
+            .is_err());
+        assert!(Fight::new_paused()
+            .try_stage("EV-2", Server::US, &stages)
+            .is_err());
+    }
 }
@@ -0,0 +1,437 @@
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::events::SubTaskExtraInfo;
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Facility {
+    /// 制造站
+    Mfg,
+    /// 贸易站
+    Trade,
+    /// 发电站
+    Power,
+    /// 控制中枢
+    Control,
+    /// 会客室
+    Reception,
+    /// 办公室
+    Office,
+    /// 宿舍
+    Dorm,
+}
+
+impl Facility {
+    /// 默认的换班顺序
+    pub const ALL: [Facility; 7] = [
+        Facility::Mfg,
+        Facility::Trade,
+        Facility::Power,
+        Facility::Control,
+        Facility::Reception,
+        Facility::Office,
+        Facility::Dorm,
+    ];
+}
+
+impl AsRef<str> for Facility {
+    fn as_ref(&self) -> &str {
+        match self {
+            Facility::Mfg => "Mfg",
+            Facility::Trade => "Trade",
+            Facility::Power => "Power",
+            Facility::Control => "Control",
+            Facility::Reception => "Reception",
+            Facility::Office => "Office",
+            Facility::Dorm => "Dorm",
+        }
+    }
+}
+
+impl TryFrom<&str> for Facility {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Facility::ALL
+            .into_iter()
+            .find(|f| f.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown facility: {s}"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DroneUsage {
+    /// 不使用无人机
+    #[serde(rename = "_NotUse")]
+    NotUse,
+    /// 贸易站-龙门币
+    Money,
+    /// 贸易站-合成玉
+    SyntheticJade,
+    /// 制造站-经验书
+    CombatRecord,
+    /// 制造站-赤金
+    PureGold,
+    /// 制造站-源石碎片
+    OriginStone,
+    /// 制造站-芯片组
+    Chip,
+}
+
+impl DroneUsage {
+    pub const ALL: [DroneUsage; 7] = [
+        DroneUsage::NotUse,
+        DroneUsage::Money,
+        DroneUsage::SyntheticJade,
+        DroneUsage::CombatRecord,
+        DroneUsage::PureGold,
+        DroneUsage::OriginStone,
+        DroneUsage::Chip,
+    ];
+}
+
+impl AsRef<str> for DroneUsage {
+    fn as_ref(&self) -> &str {
+        match self {
+            DroneUsage::NotUse => "_NotUse",
+            DroneUsage::Money => "Money",
+            DroneUsage::SyntheticJade => "SyntheticJade",
+            DroneUsage::CombatRecord => "CombatRecord",
+            DroneUsage::PureGold => "PureGold",
+            DroneUsage::OriginStone => "OriginStone",
+            DroneUsage::Chip => "Chip",
+        }
+    }
+}
+
+impl TryFrom<&str> for DroneUsage {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        DroneUsage::ALL
+            .into_iter()
+            .find(|d| d.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown drone usage: {s}"))
+    }
+}
+
+/// 基建换班
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct Infrast<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+
+    mode: usize,
+    facility: Vec<String>,
+    drones: String,
+    threshold: f64,
+    replenish: bool,
+
+    dorm_notstationed_enabled: bool,
+    dorm_trust_enabled: bool,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    filename: String,
+    plan_index: usize,
+}
+
+impl<T: State> Default for Infrast<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> Infrast<T> {
+    pub fn new() -> Self {
+        Infrast {
+            _phantom: PhantomData,
+            id: None,
+            mode: 0,
+            facility: Facility::ALL
+                .iter()
+                .map(|f| f.as_ref().to_string())
+                .collect(),
+            drones: DroneUsage::NotUse.as_ref().to_string(),
+            threshold: 0.3,
+            replenish: false,
+            dorm_notstationed_enabled: false,
+            dorm_trust_enabled: false,
+            filename: String::new(),
+            plan_index: 0,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+
+    /// 要换班的设施（有序），默认全部设施
+    pub fn facility(mut self, facility: Vec<Facility>) -> Self {
+        self.facility = facility.iter().map(|f| f.as_ref().to_string()).collect();
+        self
+    }
+
+    /// 无人机用途，可选，默认不使用
+    pub fn drones(mut self, drones: DroneUsage) -> Self {
+        self.drones = drones.as_ref().to_string();
+        self
+    }
+
+    /// 工作心情阈值，取值范围 [0, 1.0]，超出范围的值会被截断，可选，默认 0.3
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// 贸易站“源石碎片”是否自动补货，可选，默认 false
+    pub fn replenish(mut self, replenish: bool) -> Self {
+        self.replenish = replenish;
+        self
+    }
+
+    /// 是否启用宿舍“未进驻”选项，可选，默认 false
+    pub fn dorm_notstationed_enabled(mut self, dorm_notstationed_enabled: bool) -> Self {
+        self.dorm_notstationed_enabled = dorm_notstationed_enabled;
+        self
+    }
+
+    /// 是否将宿舍剩余位置填入信赖未满干员，可选，默认 false
+    pub fn dorm_trust_enabled(mut self, dorm_trust_enabled: bool) -> Self {
+        self.dorm_trust_enabled = dorm_trust_enabled;
+        self
+    }
+}
+
+impl Infrast<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    /// 使用自定义换班配置文件，及其中的方案序号。不支持运行中设置
+    pub fn custom_plan(mut self, filename: &str, plan_index: usize) -> Self {
+        self.mode = 10000;
+        self.filename = filename.to_string();
+        self.plan_index = plan_index;
+        self
+    }
+
+    pub fn start(self) -> Infrast<Running> {
+        Infrast {
+            _phantom: PhantomData,
+            id: self.id,
+            mode: self.mode,
+            facility: self.facility,
+            drones: self.drones,
+            threshold: self.threshold,
+            replenish: self.replenish,
+            dorm_notstationed_enabled: self.dorm_notstationed_enabled,
+            dorm_trust_enabled: self.dorm_trust_enabled,
+            filename: self.filename,
+            plan_index: self.plan_index,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for Infrast<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "Infrast"
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.mode {
+            0 => {}
+            10000 if !self.filename.is_empty() => {}
+            10000 => return Err(anyhow!("Custom infrast plan without filename")),
+            mode => return Err(anyhow!("Unknown infrast mode: {mode}")),
+        }
+        for (i, facility) in self.facility.iter().enumerate() {
+            Facility::try_from(facility.as_str())?;
+            if self.facility[..i].contains(facility) {
+                return Err(anyhow!("Duplicate facility: {facility}"));
+            }
+        }
+        DroneUsage::try_from(self.drones.as_str())?;
+        // The builder clamps it, only params read back can be out of range
+        if !(0.0..=1.0).contains(&self.threshold) {
+            return Err(anyhow!("Threshold {} is not in [0, 1]", self.threshold));
+        }
+        Ok(())
+    }
+}
+
+/// 一个设施房间的换班结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InfrastRoom {
+    pub facility: Facility,
+    pub index: i64,
+    pub product: Option<String>,
+    pub product_incorrect: bool,
+    pub operators: Vec<String>,
+}
+
+/// 换班报告，按进入设施的顺序记录
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InfrastReport {
+    pub rooms: Vec<InfrastRoom>,
+}
+
+impl InfrastReport {
+    fn room(&mut self, info: &SubTaskExtraInfo) -> Option<&mut InfrastRoom> {
+        let facility = info.details.facility.as_deref()?;
+        let facility = match Facility::try_from(facility) {
+            Ok(facility) => facility,
+            Err(e) => {
+                warn!("{}", e);
+                return None;
+            }
+        };
+        let index = info.details.index.unwrap_or(0);
+        let pos = match self
+            .rooms
+            .iter()
+            .position(|r| r.facility == facility && r.index == index)
+        {
+            Some(pos) => pos,
+            None => {
+                self.rooms.push(InfrastRoom {
+                    facility,
+                    index,
+                    product: None,
+                    product_incorrect: false,
+                    operators: Vec::new(),
+                });
+                self.rooms.len() - 1
+            }
+        };
+        self.rooms.get_mut(pos)
+    }
+}
+
+impl TaskReport for InfrastReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        match info.what.as_str() {
+            "EnterFacility" => {
+                self.room(info);
+            }
+            "ProductOfFacility" => {
+                if let Some(room) = self.room(info) {
+                    room.product = info.details.product.clone();
+                }
+            }
+            "ProductIncorrect" => {
+                if let Some(room) = self.room(info) {
+                    room.product_incorrect = true;
+                }
+            }
+            "CustomInfrastRoomOperators" => {
+                if let Some(room) = self.room(info) {
+                    room.operators = info.details.names.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let infrast = Infrast::new_paused()
+            .facility(vec![Facility::Mfg, Facility::Dorm])
+            .drones(DroneUsage::PureGold)
+            .threshold(1.5);
+        let json = serde_json::to_value(&infrast).unwrap();
+        assert_eq!(json["facility"], serde_json::json!(["Mfg", "Dorm"]));
+        assert_eq!(json["drones"], "PureGold");
+        assert_eq!(json["threshold"], 1.0);
+        assert!(json.get("filename").is_none());
+        assert!(infrast.check().is_ok());
+
+        let custom = Infrast::new_paused().custom_plan("plan.json", 2);
+        assert_eq!(serde_json::to_value(&custom).unwrap()["mode"], 10000);
+        assert!(custom.check().is_ok());
+        assert!(Infrast::new_paused()
+            .facility(vec![Facility::Mfg, Facility::Mfg])
+            .check()
+            .is_err());
+
+        assert_eq!(
+            serde_json::to_value(DroneUsage::NotUse).unwrap(),
+            DroneUsage::NotUse.as_ref()
+        );
+        assert_eq!(DroneUsage::try_from("Chip").unwrap(), DroneUsage::Chip);
+    }
+
+    #[test]
+    fn test_report() {
+        let info = |what: &str, details: serde_json::Value| -> SubTaskExtraInfo {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Infrast", "taskid": 1,
+                "uuid": "", "what": what, "details": details
+            }))
+            .unwrap()
+        };
+        let mut report = InfrastReport::default();
+        for (what, details) in [
+            (
+                "EnterFacility",
+                serde_json::json!({ "facility": "Mfg", "index": 0 }),
+            ),
+            (
+                "ProductOfFacility",
+                serde_json::json!({ "facility": "Mfg", "index": 0, "product": "Money" }),
+            ),
+            (
+                "ProductIncorrect",
+                serde_json::json!({ "facility": "Mfg", "index": 0 }),
+            ),
+            (
+                "EnterFacility",
+                serde_json::json!({ "facility": "Trade", "index": 1 }),
+            ),
+            (
+                "CustomInfrastRoomOperators",
+                serde_json::json!({ "facility": "Trade", "index": 1, "names": ["德克萨斯", "能天使"] }),
+            ),
+            (
+                "EnterFacility",
+                serde_json::json!({ "facility": "Garden", "index": 0 }),
+            ),
+            (
+                "EnterFacility",
+                serde_json::json!({ "facility": "Mfg", "index": 0 }),
+            ),
+        ] {
+            report.update(&info(what, details));
+        }
+
+        assert_eq!(report.rooms.len(), 2);
+        assert_eq!(
+            report.rooms[0],
+            InfrastRoom {
+                facility: Facility::Mfg,
+                index: 0,
+                product: Some("Money".to_string()),
+                product_incorrect: true,
+                operators: Vec::new(),
+            }
+        );
+        assert_eq!(report.rooms[1].facility, Facility::Trade);
+        assert_eq!(report.rooms[1].operators, ["德克萨斯", "能天使"]);
+    }
+}
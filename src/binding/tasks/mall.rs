@@ -1,58 +1,171 @@
 use std::marker::PhantomData;
 
+use anyhow::{anyhow, Result};
+use log::warn;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::binding::tasks::{Paused, Running, Server, State, StoppedTask};
+use crate::binding::events::{StageDrop, SubTaskExtraInfo, SubTaskStart};
+use crate::binding::resources::ItemMap;
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+use crate::history::{History, Stamped};
 
+/// 信用商店中的物品
+///
+/// 物品列表是固定的，资源中没有标记哪些物品会出现在信用商店；各服务器的物品名取自资源的物品数据
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ShopItem {
     LMD,
     RecruitmentPermit,
     ExpeditedPermit,
-    CarbonStick,
     FurniturePart,
+    CarbonStick,
+    CarbonBrick,
+    DrillBattleRecord,
+    FrontlineBattleRecord,
+    TacticalBattleRecord,
+    SkillSummary1,
+    SkillSummary2,
+    Orirock,
+    OrirockCube,
+    OrirockCluster,
+    SugarSubstitute,
+    Sugar,
+    SugarPack,
+    Ester,
+    Polyester,
+    PolyesterPack,
+    OrironShard,
+    Oriron,
+    OrironCluster,
+    Diketon,
+    Polyketon,
+    Aketon,
+    DamagedDevice,
+    Device,
+    IntegratedDevice,
 }
 
 impl ShopItem {
-    fn localize(&self, server: &Server) -> &'static str {
-        match server {
-            Server::CN => match self {
-                ShopItem::LMD => "龙门币",
-                ShopItem::RecruitmentPermit => "招聘",
-                ShopItem::ExpeditedPermit => "加急",
-                ShopItem::CarbonStick => "碳",
-                ShopItem::FurniturePart => "家具",
-            },
-            Server::JP => match self {
-                ShopItem::LMD => "龍門幣",
-                ShopItem::RecruitmentPermit => "採用",
-                ShopItem::ExpeditedPermit => "",
-                ShopItem::CarbonStick => "炭素",
-                ShopItem::FurniturePart => "家具",
-            },
-            Server::US => match self {
-                ShopItem::LMD => "LMD",
-                ShopItem::RecruitmentPermit => "Recruitment",
-                ShopItem::ExpeditedPermit => "Expedited",
-                ShopItem::CarbonStick => "Carbon",
-                ShopItem::FurniturePart => "Furniture",
-            },
-            Server::KR => match self {
-                ShopItem::LMD => "LMD",
-                ShopItem::RecruitmentPermit => "채용 허가증",
-                ShopItem::ExpeditedPermit => "탐색 허가증",
-                ShopItem::CarbonStick => "탄소",
-                ShopItem::FurniturePart => "가구 부품",
-            },
+    pub const ALL: [ShopItem; 29] = [
+        ShopItem::LMD,
+        ShopItem::RecruitmentPermit,
+        ShopItem::ExpeditedPermit,
+        ShopItem::FurniturePart,
+        ShopItem::CarbonStick,
+        ShopItem::CarbonBrick,
+        ShopItem::DrillBattleRecord,
+        ShopItem::FrontlineBattleRecord,
+        ShopItem::TacticalBattleRecord,
+        ShopItem::SkillSummary1,
+        ShopItem::SkillSummary2,
+        ShopItem::Orirock,
+        ShopItem::OrirockCube,
+        ShopItem::OrirockCluster,
+        ShopItem::SugarSubstitute,
+        ShopItem::Sugar,
+        ShopItem::SugarPack,
+        ShopItem::Ester,
+        ShopItem::Polyester,
+        ShopItem::PolyesterPack,
+        ShopItem::OrironShard,
+        ShopItem::Oriron,
+        ShopItem::OrironCluster,
+        ShopItem::Diketon,
+        ShopItem::Polyketon,
+        ShopItem::Aketon,
+        ShopItem::DamagedDevice,
+        ShopItem::Device,
+        ShopItem::IntegratedDevice,
+    ];
+
+    /// 物品 ID，见 `resource/item_index.json`
+    pub fn id(&self) -> &'static str {
+        match self {
+            ShopItem::LMD => "4001",
+            ShopItem::RecruitmentPermit => "7001",
+            ShopItem::ExpeditedPermit => "7002",
+            ShopItem::FurniturePart => "3401",
+            ShopItem::CarbonStick => "3112",
+            ShopItem::CarbonBrick => "3113",
+            ShopItem::DrillBattleRecord => "2001",
+            ShopItem::FrontlineBattleRecord => "2002",
+            ShopItem::TacticalBattleRecord => "2003",
+            ShopItem::SkillSummary1 => "3301",
+            ShopItem::SkillSummary2 => "3302",
+            ShopItem::Orirock => "30011",
+            ShopItem::OrirockCube => "30012",
+            ShopItem::OrirockCluster => "30013",
+            ShopItem::SugarSubstitute => "30021",
+            ShopItem::Sugar => "30022",
+            ShopItem::SugarPack => "30023",
+            ShopItem::Ester => "30031",
+            ShopItem::Polyester => "30032",
+            ShopItem::PolyesterPack => "30033",
+            ShopItem::OrironShard => "30041",
+            ShopItem::Oriron => "30042",
+            ShopItem::OrironCluster => "30043",
+            ShopItem::Diketon => "30051",
+            ShopItem::Polyketon => "30052",
+            ShopItem::Aketon => "30053",
+            ShopItem::DamagedDevice => "30061",
+            ShopItem::Device => "30062",
+            ShopItem::IntegratedDevice => "30063",
+        }
+    }
+
+    /// The item's name in `items`, which should be localized for the server, e.g. `maa.items(server)`
+    pub fn localize<'a>(&self, items: &'a ItemMap) -> Result<&'a str> {
+        match items.get(self.id()) {
+            Some(item) if !item.name.is_empty() => Ok(&item.name),
+            _ => Err(anyhow!(
+                "Shop item {:?} ({}) does not exist on this server",
+                self,
+                self.id()
+            )),
         }
     }
+
+    /// Names of the other shop items containing this item's name in `items`.
+    ///
+    /// MaaCore looks for the name as a substring of the shop text, so it matches those items too
+    pub fn overlapping<'a>(&self, items: &'a ItemMap) -> Vec<&'a str> {
+        let Ok(name) = self.localize(items) else {
+            return Vec::new();
+        };
+        ShopItem::ALL
+            .iter()
+            .filter(|other| *other != self)
+            .filter_map(|other| other.localize(items).ok())
+            .filter(|other| other.contains(name))
+            .collect()
+    }
+}
+
+fn localize_all(items: Vec<ShopItem>, item_map: &ItemMap) -> Result<Vec<String>> {
+    items
+        .iter()
+        .map(|x| {
+            let name = x.localize(item_map)?;
+            let overlapping = x.overlapping(item_map);
+            if !overlapping.is_empty() {
+                warn!(
+                    "Shop item {:?} ({}) also matches {}",
+                    x,
+                    name,
+                    overlapping.join(", ")
+                );
+            }
+            Ok(name.to_string())
+        })
+        .collect()
 }
 
 /// 领取信用及商店购物
 ///
 ///  会先有序的按 `buy_first` 购买一遍，再从左到右并避开 `blacklist` 购买第二遍，在信用溢出时则会无视黑名单从左到右购买第三遍直到不再溢出
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct Mall<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -64,6 +177,15 @@ pub struct Mall<T: State> {
     blacklist: Vec<String>,
 
     force_shopping_if_credit_full: bool,
+
+    visit_friends: bool,
+    credit_fight: bool,
+}
+
+impl<T: State> Default for Mall<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: State> Mall<T> {
@@ -75,6 +197,8 @@ impl<T: State> Mall<T> {
             buy_first: Vec::new(),
             blacklist: Vec::new(),
             force_shopping_if_credit_full: false,
+            visit_friends: true,
+            credit_fight: false,
         }
     }
 }
@@ -91,21 +215,19 @@ impl Mall<Paused> {
     }
 
     /// 设置优先购买的物品，可选，默认为空。不支持运行中设置
-    pub fn buy_first(mut self, buy_first: Vec<ShopItem>, server: &Server) -> Self {
-        self.buy_first = buy_first
-            .into_iter()
-            .map(|x| x.localize(server).to_string())
-            .collect();
-        self
+    ///
+    /// 物品名取自 `item_map`，应为所在服务器的物品，如 `maa.items(server)`；服务器上没有的物品会报错
+    pub fn buy_first(mut self, buy_first: Vec<ShopItem>, item_map: &ItemMap) -> Result<Self> {
+        self.buy_first = localize_all(buy_first, item_map)?;
+        Ok(self)
     }
 
     /// 设置黑名单，可选，默认为空。不支持运行中设置
-    pub fn blacklist(mut self, blacklist: Vec<ShopItem>, server: &Server) -> Self {
-        self.blacklist = blacklist
-            .into_iter()
-            .map(|x| x.localize(server).to_string())
-            .collect();
-        self
+    ///
+    /// 物品名取自 `item_map`，同 `buy_first`
+    pub fn blacklist(mut self, blacklist: Vec<ShopItem>, item_map: &ItemMap) -> Result<Self> {
+        self.blacklist = localize_all(blacklist, item_map)?;
+        Ok(self)
     }
 
     /// 设置信用溢出时是否强制购物，可选，默认 false。不支持运行中设置
@@ -114,6 +236,18 @@ impl Mall<Paused> {
         self
     }
 
+    /// 设置是否访问好友获取信用，可选，默认 true。不支持运行中设置
+    pub fn visit_friends(mut self, visit_friends: bool) -> Self {
+        self.visit_friends = visit_friends;
+        self
+    }
+
+    /// 设置是否借助战打一局 OF-1 获取信用，可选，默认 false。不支持运行中设置
+    pub fn credit_fight(mut self, credit_fight: bool) -> Self {
+        self.credit_fight = credit_fight;
+        self
+    }
+
     pub fn start(self) -> Mall<Running> {
         Mall {
             _phantom: PhantomData,
@@ -122,6 +256,8 @@ impl Mall<Paused> {
             buy_first: self.buy_first,
             blacklist: self.blacklist,
             force_shopping_if_credit_full: self.force_shopping_if_credit_full,
+            visit_friends: self.visit_friends,
+            credit_fight: self.credit_fight,
         }
     }
 }
@@ -135,3 +271,146 @@ impl<'a> StoppedTask<'a> for Mall<Paused> {
         "Mall"
     }
 }
+
+/// 借助战打 OF-1 的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreditFight {
+    pub stars: Option<i64>,
+    pub drops: Vec<StageDrop>,
+}
+
+/// 信用商店的结果，取自借助战的 `StageDrops` 信息及 `VisitNext` 子任务
+///
+/// MaaCore 不回报信用点数及所购物品，故不在其中
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MallReport {
+    pub friend_visits: usize,
+    pub credit_fight: Option<CreditFight>,
+}
+
+impl MallReport {
+    const HISTORY_NAME: &'static str = "mall";
+
+    pub fn save(&self, history: &History) -> Result<()> {
+        history.append(Self::HISTORY_NAME, &Stamped::now(self))
+    }
+
+    pub fn records(history: &History) -> Result<Vec<Stamped<MallReport>>> {
+        history.records(Self::HISTORY_NAME)
+    }
+}
+
+impl TaskReport for MallReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        if info.what == "StageDrops" {
+            self.credit_fight = Some(CreditFight {
+                stars: info.details.stars,
+                drops: info.details.drops.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    fn sub_task_start(&mut self, info: &SubTaskStart) {
+        if info.details.task.as_deref() == Some("VisitNext") {
+            self.friend_visits += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::resources::Item;
+    use serde_json::json;
+
+    #[test]
+    fn test_localize() {
+        let items = ItemMap::from([
+            (
+                "4001".to_string(),
+                Item {
+                    name: "龍門幣".to_string(),
+                    ..Default::default()
+                },
+            ),
+            ("7002".to_string(), Item::default()),
+        ]);
+        let mall = Mall::new_paused()
+            .buy_first(vec![ShopItem::LMD], &items)
+            .unwrap();
+        assert_eq!(mall.buy_first, ["龍門幣"]);
+        assert!(ShopItem::ExpeditedPermit.localize(&items).is_err());
+        assert!(Mall::new_paused()
+            .blacklist(vec![ShopItem::CarbonStick], &items)
+            .is_err());
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let items = [
+            (ShopItem::DamagedDevice, "破损装置"),
+            (ShopItem::Device, "装置"),
+            (ShopItem::IntegratedDevice, "全新装置"),
+            (ShopItem::Sugar, "糖"),
+            (ShopItem::SugarPack, "糖聚块"),
+            (ShopItem::LMD, "龙门币"),
+        ]
+        .into_iter()
+        .map(|(item, name)| {
+            let item_data = Item {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            (item.id().to_string(), item_data)
+        })
+        .collect::<ItemMap>();
+        assert_eq!(
+            ShopItem::Device.overlapping(&items),
+            ["破损装置", "全新装置"]
+        );
+        assert_eq!(ShopItem::Sugar.overlapping(&items), ["糖聚块"]);
+        assert!(ShopItem::DamagedDevice.overlapping(&items).is_empty());
+        assert!(ShopItem::LMD.overlapping(&items).is_empty());
+        assert!(ShopItem::Oriron.overlapping(&items).is_empty());
+        // Still allowed, only warned about
+        let mall = Mall::new_paused()
+            .blacklist(vec![ShopItem::Device], &items)
+            .unwrap();
+        assert_eq!(mall.blacklist, ["装置"]);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = MallReport::default();
+        let visit = |task: &str| -> SubTaskStart {
+            serde_json::from_value(json!({
+                "class": "asst::ProcessTask", "subtask": "ProcessTask", "taskchain": "Mall",
+                "taskid": 1, "uuid": "", "details": { "task": task, "exec_times": 1, "max_times": 10 }
+            }))
+            .unwrap()
+        };
+        for task in ["Friends", "VisitNext", "VisitNext", "VisitLimited"] {
+            report.sub_task_start(&visit(task));
+        }
+        // The drops of the OF-1 fight, reported by the stage drops plugin as for any fight
+        let drops: SubTaskExtraInfo = serde_json::from_value(json!({
+            "class": "asst::StageDropsTaskPlugin", "subtask": "ProcessTask",
+            "taskchain": "Mall", "taskid": 1, "uuid": "", "what": "StageDrops",
+            "details": {
+                "stage": { "stageCode": "OF-1", "stageId": "act10d5_01" },
+                "stars": 3,
+                "drops": [
+                    { "dropType": "NORMAL_DROP", "itemId": "4001", "itemName": "龙门币", "quantity": 12 }
+                ],
+                "stats": []
+            }
+        }))
+        .unwrap();
+        report.update(&drops);
+
+        assert_eq!(report.friend_visits, 2);
+        let fight = report.credit_fight.unwrap();
+        assert_eq!(fight.stars, Some(3));
+        assert_eq!(fight.drops[0].item_name, "龙门币");
+    }
+}
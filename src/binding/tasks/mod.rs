@@ -1,19 +1,42 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
 pub use close_down::*;
+pub use copilot::*;
+pub use custom::*;
+pub use depot::*;
 pub use fight::*;
+pub use infrast::*;
 pub use mall::*;
+pub use oper_box::*;
+pub use plan::*;
+pub use reclamation::*;
 pub use recruit::*;
+pub use recruit_policy::*;
+pub use roguelike::*;
+pub use sanity_plan::*;
+pub use sss_copilot::*;
 pub use startup::*;
 pub use award::*;
 
 use crate::binding::connection::MAAConnection;
+use crate::binding::events::{AsstMsg, Events, SubTaskExtraInfo, SubTaskStart};
 
 mod close_down;
+mod copilot;
+mod custom;
+mod depot;
 mod fight;
+mod infrast;
 mod mall;
+mod oper_box;
+mod plan;
+mod reclamation;
 mod recruit;
+mod recruit_policy;
+mod sanity_plan;
+mod sss_copilot;
 mod startup;
 mod award;
 mod roguelike;
@@ -27,14 +50,100 @@ pub trait StoppedTask<'a>: Deserialize<'a> + Serialize {
 
     fn name(&self) -> &'static str;
 
+    /// Check what can be checked without a connection, such as ranges and option names
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check the params before they are sent to MaaCore, called by `append_in`
+    fn validate(&self, _maa: &MAAConnection) -> Result<()> {
+        self.check()
+    }
+
+    /// Read back the params sent to MaaCore, unknown fields and invalid values are errors
+    fn from_json(json: &'a str) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let task: Self = serde_json::from_str(json)?;
+        task.check()?;
+        Ok(task)
+    }
+
     fn append_in(mut self, maa: &mut MAAConnection) -> Result<Self> {
+        self.validate(maa)?;
         let id = maa.append_task(&self)?;
         self.set_id(id);
         Ok(self)
     }
 }
 
-#[derive(Copy, Clone)]
+/// The outcome of a task, built from the extra info it reports while running
+pub trait TaskReport: Default {
+    fn update(&mut self, info: &SubTaskExtraInfo);
+
+    /// For tasks that only report progress through the name of their sub tasks
+    fn sub_task_start(&mut self, _info: &SubTaskStart) {}
+}
+
+/// Build a report from the events of task `task_id` until its task chain ends.
+///
+/// Subscribe before starting the connection, so that no event is missed:
+/// ```ignore
+/// let report = collect_report::<InfrastReport>(maa.subscribe(), id);
+/// ```
+pub async fn collect_report<R: TaskReport>(
+    events: broadcast::Receiver<Events>,
+    task_id: usize,
+) -> Result<R> {
+    follow_report(R::default(), events, task_id).await
+}
+
+/// Same as `collect_report`, but starting from `report`, for reports that need some setup
+pub async fn follow_report<R: TaskReport>(
+    mut report: R,
+    mut events: broadcast::Receiver<Events>,
+    task_id: usize,
+) -> Result<R> {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                return Err(anyhow!("Missed {n} events of task {task_id}"))
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                return Err(anyhow!("Connection closed before task {task_id} ended"))
+            }
+        };
+        if apply_event(&mut report, event, task_id)? {
+            return Ok(report);
+        }
+    }
+}
+
+/// Feed `event` to `report` if it belongs to task `task_id`, returns whether the task ended
+pub fn apply_event<R: TaskReport>(report: &mut R, event: Events, task_id: usize) -> Result<bool> {
+    if event.params["taskid"].as_u64() != Some(task_id as u64) {
+        return Ok(false);
+    }
+    match event.type_ {
+        AsstMsg::SubTaskExtraInfo => {
+            let info: SubTaskExtraInfo = serde_json::from_value(event.params)?;
+            report.update(&info);
+        }
+        AsstMsg::SubTaskStart => {
+            let info: SubTaskStart = serde_json::from_value(event.params)?;
+            report.sub_task_start(&info);
+        }
+        AsstMsg::TaskChainCompleted | AsstMsg::TaskChainError | AsstMsg::TaskChainStopped => {
+            return Ok(true)
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ClientType {
     Official,
     Bilibili,
@@ -57,7 +166,57 @@ impl AsRef<str> for ClientType {
     }
 }
 
-#[derive(Copy, Clone)]
+impl ClientType {
+    pub const ALL: [ClientType; 6] = [
+        ClientType::Official,
+        ClientType::Bilibili,
+        ClientType::Twxy,
+        ClientType::YoStarEN,
+        ClientType::YoStarJP,
+        ClientType::YoStarKR,
+    ];
+
+    /// The directory under `resource/global` holding the resources of the client, if any
+    pub fn resource_dir(&self) -> Option<&'static str> {
+        match self {
+            ClientType::Official | ClientType::Bilibili => None,
+            ClientType::Twxy => Some("txwy"),
+            ClientType::YoStarEN => Some("YoStarEN"),
+            ClientType::YoStarJP => Some("YoStarJP"),
+            ClientType::YoStarKR => Some("YoStarKR"),
+        }
+    }
+
+    /// The Android package of the client
+    pub fn package(&self) -> &'static str {
+        match self {
+            ClientType::Official => "com.hypergryph.arknights",
+            ClientType::Bilibili => "com.hypergryph.arknights.bilibili",
+            ClientType::Twxy => "tw.txwy.and.arknights",
+            ClientType::YoStarEN => "com.YoStarEN.Arknights",
+            ClientType::YoStarJP => "com.YoStarJP.Arknights",
+            ClientType::YoStarKR => "com.YoStarKR.Arknights",
+        }
+    }
+
+    /// The launcher activity of the client, the same for all of them
+    pub fn activity(&self) -> &'static str {
+        "com.u8.sdk.U8UnityContext"
+    }
+}
+
+impl TryFrom<&str> for ClientType {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        ClientType::ALL
+            .into_iter()
+            .find(|client| client.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown client type: {s}"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Server {
     CN,
     JP,
@@ -76,12 +235,133 @@ impl AsRef<str> for Server {
     }
 }
 
+impl TryFrom<&str> for Server {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        [Server::CN, Server::JP, Server::KR, Server::US]
+            .into_iter()
+            .find(|server| server.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown server: {s}"))
+    }
+}
+
 pub trait State {}
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Running {}
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Paused {}
 
 impl State for Running {}
 
 impl State for Paused {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+
+    fn round_trip<T>(task: T)
+    where
+        T: for<'a> StoppedTask<'a> + PartialEq + Debug,
+    {
+        let json = task.to_json();
+        let read = T::from_json(&json).unwrap_or_else(|e| panic!("{json}: {e}"));
+        assert_eq!(read, task);
+        // Maps may serialize in another order, so compare values
+        assert_eq!(
+            serde_json::to_value(&read).unwrap(),
+            serde_json::to_value(&task).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(
+            StartUp::new_paused()
+                .set_client_type(ClientType::Bilibili)
+                .set_start_game_enabled(true)
+                .set_account_name("4567"),
+        );
+        round_trip(CloseDown::new_paused());
+        round_trip(
+            Fight::new_paused()
+                .stage("CE-6")
+                .use_medicine(2)
+                .stop_when_drop(HashMap::from([("30012", 5), ("30062", 1)]))
+                .report_to_penguin("12345678".to_string())
+                .server(Server::US)
+                .client_type(ClientType::YoStarEN),
+        );
+        round_trip(
+            Recruit::new_paused()
+                .refresh(true)
+                .times(4)
+                .expedite(2)
+                .recruitment_time(540, 460, 540, 540)
+                .report_to_yituliu("abc".to_string()),
+        );
+        round_trip(
+            Infrast::new_paused()
+                .facility(vec![Facility::Mfg, Facility::Dorm])
+                .drones(DroneUsage::Money)
+                .threshold(0.5)
+                .custom_plan("plan.json", 1),
+        );
+        round_trip(
+            Mall::new_paused()
+                .shopping(true)
+                .force_buy_when_full(true)
+                .credit_fight(true),
+        );
+        round_trip(Award::new_paused().mail(true).orundum(true));
+        round_trip(Copilot::new_paused().filename("job.json").formation(true));
+        round_trip(SSSCopilot::new_paused().filename("sss.json").loop_times(2));
+        round_trip(Depot::new_paused());
+        round_trip(OperBox::new_paused());
+        round_trip(
+            ReclamationAlgorithm::new_paused()
+                .theme(ReclamationTheme::Tales)
+                .mode(ReclamationMode::FarmCraft)
+                .tools_to_craft(vec!["荧光棒"])
+                .num_craft_batches(8),
+        );
+        round_trip(
+            RogueLike::new_paused()
+                .theme(RogueLikeTheme::Mizuki)
+                .squad(RogueLikeSquad::Research)
+                .core_char("棘刺")
+                .refresh_trader_with_dice(true),
+        );
+        round_trip(Custom::new_paused().task_names(vec!["Award"]));
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert!(Fight::<Paused>::from_json(r#"{"server": "EU"}"#).is_err());
+        assert!(Fight::<Paused>::from_json(r#"{"client_type": "Steam"}"#).is_err());
+        assert!(Fight::<Paused>::from_json(r#"{"drop": {"30012": 0}}"#).is_err());
+        assert!(Recruit::<Paused>::from_json(r#"{"recruitment_time": {"2": 540}}"#).is_err());
+        assert!(Recruit::<Paused>::from_json(r#"{"recruitment_time": {"3": 545}}"#).is_err());
+        assert!(Recruit::<Paused>::from_json(r#"{"select": [7]}"#).is_err());
+        assert!(Infrast::<Paused>::from_json(r#"{"drones": "Coffee"}"#).is_err());
+        assert!(Infrast::<Paused>::from_json(r#"{"facility": ["Mfg", "Mfg"]}"#).is_err());
+        assert!(Infrast::<Paused>::from_json(r#"{"threshold": 1.5}"#).is_err());
+        assert!(Infrast::<Paused>::from_json(r#"{"mode": 10000}"#).is_err());
+        assert!(StartUp::<Paused>::from_json(
+            r#"{"client_type": "YoStarEN", "account_name": "4567"}"#
+        )
+        .is_err());
+        assert!(Award::<Paused>::from_json(r#"{"unknown": true}"#).is_err());
+        assert!(Custom::<Paused>::from_json("{}").is_err());
+        assert!(
+            SSSCopilot::<Paused>::from_json(r#"{"filename": "a.json", "loop_times": 0}"#).is_err()
+        );
+        assert!(ReclamationAlgorithm::<Paused>::from_json(r#"{"theme": "Water"}"#).is_err());
+        assert!(RogueLike::<Paused>::from_json(r#"{"theme": "Phantom", "mode": 5}"#).is_err());
+        assert!(Mall::<Paused>::from_json("{}").is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::events::{BoxOperator, SubTaskExtraInfo};
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+use crate::history::History;
+
+/// 干员识别
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct OperBox<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+}
+
+impl<T: State> Default for OperBox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> OperBox<T> {
+    pub fn new() -> Self {
+        OperBox {
+            _phantom: PhantomData,
+            id: None,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl OperBox<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    pub fn start(self) -> OperBox<Running> {
+        OperBox {
+            _phantom: PhantomData,
+            id: self.id,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for OperBox<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "OperBox"
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RosterOperator {
+    pub id: String,
+    pub name: String,
+    pub own: bool,
+    pub rarity: i64,
+    /// 精英化阶段，未拥有时为 0
+    pub elite: i64,
+    pub level: i64,
+    pub potential: i64,
+}
+
+/// 全部干员，包括未拥有的
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Roster {
+    pub operators: Vec<RosterOperator>,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Roster {
+    const HISTORY_NAME: &'static str = "oper_box";
+
+    /// Merge the owned operators, which carry the levels, into the list of all operators.
+    ///
+    /// Owned operators missing from `all_oper` are added after it.
+    pub fn from_opers(all_oper: &[BoxOperator], own_opers: &[BoxOperator]) -> Self {
+        let roster_operator = |oper: &BoxOperator, own: bool| RosterOperator {
+            id: oper.id.clone(),
+            name: oper.name.clone(),
+            own,
+            rarity: oper.rarity,
+            elite: oper.elite,
+            level: oper.level,
+            potential: oper.potential,
+        };
+        let mut operators = all_oper
+            .iter()
+            .map(|oper| match own_opers.iter().find(|o| o.id == oper.id) {
+                Some(owned) => roster_operator(owned, true),
+                None => roster_operator(oper, oper.own),
+            })
+            .collect::<Vec<_>>();
+        operators.extend(
+            own_opers
+                .iter()
+                .filter(|o| !all_oper.iter().any(|a| a.id == o.id))
+                .map(|o| roster_operator(o, true)),
+        );
+        Roster { operators }
+    }
+
+    pub fn owned(&self) -> impl Iterator<Item = &RosterOperator> {
+        self.operators.iter().filter(|o| o.own)
+    }
+
+    /// Find an operator by id or by name
+    pub fn get(&self, id_or_name: &str) -> Option<&RosterOperator> {
+        self.operators
+            .iter()
+            .find(|o| o.id == id_or_name || o.name == id_or_name)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.operators).unwrap()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("id,name,own,rarity,elite,level,potential\n");
+        for o in &self.operators {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                csv_field(&o.id),
+                csv_field(&o.name),
+                o.own,
+                o.rarity,
+                o.elite,
+                o.level,
+                o.potential
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    pub fn save(&self, history: &History) -> Result<()> {
+        history.save(Self::HISTORY_NAME, self)
+    }
+
+    pub fn load(history: &History) -> Result<Option<Self>> {
+        history.load(Self::HISTORY_NAME)
+    }
+}
+
+/// 干员识别结果，识别完成前 `roster` 为已识别的部分
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperBoxReport {
+    pub done: bool,
+    pub roster: Roster,
+}
+
+impl TaskReport for OperBoxReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        if info.what != "OperBoxInfo" {
+            return;
+        }
+        self.roster = Roster::from_opers(&info.details.all_oper, &info.details.own_opers);
+        self.done = info.details.done.unwrap_or(false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn oper(id: &str, name: &str, own: bool, elite: i64) -> BoxOperator {
+        BoxOperator {
+            id: id.to_string(),
+            name: name.to_string(),
+            own,
+            rarity: 4,
+            elite,
+            level: if own { 50 } else { 0 },
+            potential: 1,
+        }
+    }
+
+    #[test]
+    fn test_from_opers() {
+        let all_oper = [
+            oper("char_102_texas", "德克萨斯", true, 0),
+            oper("char_103_angel", "能天使", false, 0),
+        ];
+        let own_opers = [
+            oper("char_102_texas", "德克萨斯", true, 2),
+            oper("char_4000_new", "新干员", true, 1),
+        ];
+        let roster = Roster::from_opers(&all_oper, &own_opers);
+        assert_eq!(roster.operators.len(), 3);
+        assert_eq!(roster.get("德克萨斯").unwrap().elite, 2);
+        assert!(!roster.get("char_103_angel").unwrap().own);
+        assert_eq!(roster.get("char_4000_new").unwrap().elite, 1);
+        assert_eq!(roster.owned().count(), 2);
+
+        // Without `all_oper`, the owned operators are still there
+        let roster = Roster::from_opers(&[], &own_opers);
+        assert_eq!(roster.owned().count(), 2);
+    }
+
+    #[test]
+    fn test_export() {
+        let roster = Roster::from_opers(&[oper("char_1", "W, \"the\" one", true, 2)], &[]);
+        assert_eq!(
+            roster.to_csv(),
+            "id,name,own,rarity,elite,level,potential\n\
+             char_1,\"W, \"\"the\"\" one\",true,4,2,50,1\n"
+        );
+        let json: Vec<RosterOperator> = serde_json::from_str(&roster.to_json()).unwrap();
+        assert_eq!(json, roster.operators);
+    }
+}
@@ -0,0 +1,405 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::binding::app::{AppControl, AppStep};
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::{AsstMsg, Events};
+use crate::binding::tasks::{
+    Award, CloseDown, Copilot, Custom, Depot, Fight, Infrast, Mall, OperBox, Paused,
+    ReclamationAlgorithm, Recruit, RogueLike, SSSCopilot, StartUp, StoppedTask,
+};
+
+/// Any task that can be stored in a plan, tagged by its MaaCore task name, or `App` for
+/// controlling the client over adb
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "params")]
+pub enum PlanTask {
+    StartUp(StartUp<Paused>),
+    CloseDown(CloseDown<Paused>),
+    Fight(Fight<Paused>),
+    Recruit(Recruit<Paused>),
+    Infrast(Infrast<Paused>),
+    Mall(Mall<Paused>),
+    Award(Award<Paused>),
+    Copilot(Copilot<Paused>),
+    SSSCopilot(SSSCopilot<Paused>),
+    Depot(Depot<Paused>),
+    OperBox(OperBox<Paused>),
+    ReclamationAlgorithm(ReclamationAlgorithm<Paused>),
+    Roguelike(RogueLike<Paused>),
+    Custom(Custom<Paused>),
+    App(AppStep),
+}
+
+fn validate<'a, T: StoppedTask<'a>>(task: &T, maa: &MAAConnection) -> Result<()> {
+    task.validate(maa)
+        .map_err(|e| anyhow!("Invalid {} task: {e}", task.name()))
+}
+
+fn append<'a, T: StoppedTask<'a>>(task: &T, maa: &MAAConnection) -> Result<usize> {
+    validate(task, maa)?;
+    maa.append_task(task)
+}
+
+impl PlanTask {
+    pub fn validate(&self, maa: &MAAConnection) -> Result<()> {
+        match self {
+            PlanTask::StartUp(task) => validate(task, maa),
+            PlanTask::CloseDown(task) => validate(task, maa),
+            PlanTask::Fight(task) => validate(task, maa),
+            PlanTask::Recruit(task) => validate(task, maa),
+            PlanTask::Infrast(task) => validate(task, maa),
+            PlanTask::Mall(task) => validate(task, maa),
+            PlanTask::Award(task) => validate(task, maa),
+            PlanTask::Copilot(task) => validate(task, maa),
+            PlanTask::SSSCopilot(task) => validate(task, maa),
+            PlanTask::Depot(task) => validate(task, maa),
+            PlanTask::OperBox(task) => validate(task, maa),
+            PlanTask::ReclamationAlgorithm(task) => validate(task, maa),
+            PlanTask::Roguelike(task) => validate(task, maa),
+            PlanTask::Custom(task) => validate(task, maa),
+            PlanTask::App(step) => step
+                .validate()
+                .map_err(|e| anyhow!("Invalid App step: {e}")),
+        }
+    }
+
+    /// Validate and append the task, returning its id. `App` steps can't be appended, they
+    /// only run through `TaskPlan::run`.
+    pub fn append_in(&self, maa: &MAAConnection) -> Result<usize> {
+        match self {
+            PlanTask::StartUp(task) => append(task, maa),
+            PlanTask::CloseDown(task) => append(task, maa),
+            PlanTask::Fight(task) => append(task, maa),
+            PlanTask::Recruit(task) => append(task, maa),
+            PlanTask::Infrast(task) => append(task, maa),
+            PlanTask::Mall(task) => append(task, maa),
+            PlanTask::Award(task) => append(task, maa),
+            PlanTask::Copilot(task) => append(task, maa),
+            PlanTask::SSSCopilot(task) => append(task, maa),
+            PlanTask::Depot(task) => append(task, maa),
+            PlanTask::OperBox(task) => append(task, maa),
+            PlanTask::ReclamationAlgorithm(task) => append(task, maa),
+            PlanTask::Roguelike(task) => append(task, maa),
+            PlanTask::Custom(task) => append(task, maa),
+            PlanTask::App(_) => Err(anyhow!("App steps can only run through TaskPlan::run")),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanItem {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub task: PlanTask,
+}
+
+/// An ordered list of tasks, such as a daily routine.
+///
+/// ```toml
+/// [[tasks]]
+/// type = "StartUp"
+/// params = { client_type = "Official", start_game_enabled = true }
+///
+/// [[tasks]]
+/// type = "Fight"
+/// enabled = false
+/// params = { stage = "1-7" }
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskPlan {
+    #[serde(default)]
+    pub tasks: Vec<PlanItem>,
+}
+
+impl TaskPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(mut self, task: PlanTask) -> Self {
+        self.tasks.push(PlanItem {
+            enabled: true,
+            task,
+        });
+        self
+    }
+
+    pub fn push_disabled(mut self, task: PlanTask) -> Self {
+        self.tasks.push(PlanItem {
+            enabled: false,
+            task,
+        });
+        self
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Load a plan, the format is chosen by the extension (`.json` or `.toml`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let plan = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&content),
+            Some("toml") => Self::from_toml(&content),
+            _ => return Err(anyhow!("Unknown plan format: {}", path.display())),
+        };
+        plan.map_err(|e| anyhow!("Invalid plan {}: {e}", path.display()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            Some("toml") => self.to_toml()?,
+            _ => return Err(anyhow!("Unknown plan format: {}", path.display())),
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &PlanTask> {
+        self.tasks.iter().filter(|t| t.enabled).map(|t| &t.task)
+    }
+
+    /// Append the enabled tasks in order, returning their ids.
+    ///
+    /// Every task is validated first, so an invalid plan appends nothing. If MaaCore refuses
+    /// a task anyway, the error is a [`PartialAppend`] with the tasks already appended, which
+    /// are left in the queue.
+    pub fn append_in(&self, maa: &MAAConnection) -> Result<Vec<usize>> {
+        if self.enabled().any(|task| matches!(task, PlanTask::App(_))) {
+            return Err(anyhow!("The plan has App steps, run it with TaskPlan::run"));
+        }
+        for task in self.enabled() {
+            task.validate(maa)?;
+        }
+        let mut ids = Vec::new();
+        for task in self.enabled() {
+            match task.append_in(maa) {
+                Ok(id) => ids.push(id),
+                Err(error) if ids.is_empty() => return Err(error),
+                Err(error) => return Err(PartialAppend { ids, error }.into()),
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Run the plan to the end, returning the ids of its tasks.
+    ///
+    /// The tasks between two `App` steps are appended and run, and each `App` step runs once
+    /// the tasks before it are done. A task ending with `TaskChainError` stops the connection
+    /// and fails the run, the steps after it are not run.
+    pub async fn run(&self, maa: &MAAConnection, app: &AppControl) -> Result<Vec<usize>> {
+        for task in self.enabled() {
+            task.validate(maa)?;
+        }
+        let mut ids = Vec::new();
+        let mut pending = 0;
+        for task in self.enabled() {
+            match task {
+                PlanTask::App(step) => {
+                    if pending < ids.len() {
+                        wait_tasks(maa, &ids[pending..]).await?;
+                        pending = ids.len();
+                    }
+                    app.run(step).await?;
+                }
+                task => ids.push(task.append_in(maa)?),
+            }
+        }
+        if pending < ids.len() {
+            wait_tasks(maa, &ids[pending..]).await?;
+        }
+        Ok(ids)
+    }
+}
+
+/// Appending a plan failed after some of its tasks were appended.
+///
+/// The tasks `ids` are still queued, stopping the connection drops them along with
+/// everything else in the queue.
+#[derive(Debug)]
+pub struct PartialAppend {
+    pub ids: Vec<usize>,
+    pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for PartialAppend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, tasks {:?} were appended", self.error, self.ids)
+    }
+}
+
+impl std::error::Error for PartialAppend {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Start the appended tasks and wait until they are done, stopping at the first failed one
+async fn wait_tasks(maa: &MAAConnection, ids: &[usize]) -> Result<()> {
+    let mut events = maa.subscribe();
+    maa.start()?;
+    let ret = wait_events(&mut events, ids).await;
+    if ret.is_err() {
+        maa.stop();
+    }
+    ret
+}
+
+/// Wait until every task of `ids` is completed, an error if one of them fails or is stopped
+async fn wait_events(events: &mut broadcast::Receiver<Events>, ids: &[usize]) -> Result<()> {
+    let mut left = ids.to_vec();
+    while !left.is_empty() {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                return Err(anyhow!("Task plan missed {n} events"))
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                return Err(anyhow!("Connection closed before the tasks were done"))
+            }
+        };
+        let Some(taskid) = event.params["taskid"].as_u64() else {
+            continue;
+        };
+        let Some(index) = left.iter().position(|id| *id as u64 == taskid) else {
+            continue;
+        };
+        let taskchain = event.params["taskchain"].as_str().unwrap_or_default();
+        match event.type_ {
+            AsstMsg::TaskChainCompleted => {
+                left.remove(index);
+            }
+            AsstMsg::TaskChainError => {
+                return Err(anyhow!("Task {taskchain} ({taskid}) failed"));
+            }
+            AsstMsg::TaskChainStopped => {
+                return Err(anyhow!("Task {taskchain} ({taskid}) was stopped"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::app::AppAction;
+    use crate::binding::tasks::ClientType;
+
+    #[test]
+    fn test_plan_formats() {
+        let plan = TaskPlan::from_toml(
+            r#"
+            [[tasks]]
+            type = "StartUp"
+            params = { client_type = "Bilibili", start_game_enabled = true }
+
+            [[tasks]]
+            type = "Fight"
+            enabled = false
+            params = { stage = "1-7" }
+
+            [[tasks]]
+            type = "Custom"
+            params = { task_names = ["Award"] }
+
+            [[tasks]]
+            type = "App"
+            params = { action = "force_stop", client_type = "Bilibili" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(plan.tasks.len(), 4);
+        assert_eq!(plan.enabled().count(), 3);
+        assert_eq!(
+            plan.tasks[3].task,
+            PlanTask::App(AppStep::new(AppAction::ForceStop, ClientType::Bilibili))
+        );
+        assert_eq!(
+            plan.tasks[1].task,
+            PlanTask::Fight(Fight::new_paused().stage("1-7"))
+        );
+
+        assert_eq!(TaskPlan::from_json(&plan.to_json()).unwrap(), plan);
+        assert_eq!(TaskPlan::from_toml(&plan.to_toml().unwrap()).unwrap(), plan);
+        assert!(TaskPlan::from_json(r#"{"tasks": [{"type": "Unknown"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_partial_append() {
+        let error: anyhow::Error = PartialAppend {
+            ids: vec![3, 4],
+            error: anyhow!("Append task failed"),
+        }
+        .into();
+        assert_eq!(
+            error.to_string(),
+            "Append task failed, tasks [3, 4] were appended"
+        );
+        assert_eq!(error.downcast::<PartialAppend>().unwrap().ids, [3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_events() {
+        let event = |type_, taskid: usize| Events {
+            type_,
+            params: serde_json::json!({ "taskchain": "Fight", "taskid": taskid }),
+        };
+        let (tx, mut rx) = broadcast::channel(16);
+        for e in [
+            event(AsstMsg::TaskChainStart, 1),
+            event(AsstMsg::TaskChainCompleted, 1),
+            event(AsstMsg::TaskChainError, 3),
+            event(AsstMsg::TaskChainCompleted, 2),
+        ] {
+            tx.send(e).unwrap();
+        }
+        wait_events(&mut rx, &[1, 2]).await.unwrap();
+
+        for e in [
+            event(AsstMsg::TaskChainCompleted, 4),
+            event(AsstMsg::TaskChainError, 5),
+            event(AsstMsg::TaskChainCompleted, 6),
+        ] {
+            tx.send(e).unwrap();
+        }
+        let err = wait_events(&mut rx, &[4, 5, 6]).await.unwrap_err();
+        assert_eq!(err.to_string(), "Task Fight (5) failed");
+
+        // The completion of a task may be among the missed events
+        let (tx, mut rx) = broadcast::channel(1);
+        tx.send(event(AsstMsg::TaskChainCompleted, 1)).unwrap();
+        tx.send(event(AsstMsg::TaskChainStart, 2)).unwrap();
+        assert!(wait_events(&mut rx, &[1, 2]).await.is_err());
+
+        drop(tx);
+        assert!(wait_events(&mut rx, &[7]).await.is_err());
+    }
+}
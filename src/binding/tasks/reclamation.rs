@@ -0,0 +1,266 @@
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::events::{SubTaskExtraInfo, SubTaskStart};
+use crate::binding::tasks::{Paused, Running, State, StoppedTask, TaskReport};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReclamationTheme {
+    /// 沙中之火
+    Fire,
+    /// 沙洲遗闻
+    Tales,
+}
+
+impl ReclamationTheme {
+    pub const ALL: [ReclamationTheme; 2] = [ReclamationTheme::Fire, ReclamationTheme::Tales];
+}
+
+impl AsRef<str> for ReclamationTheme {
+    fn as_ref(&self) -> &str {
+        match self {
+            ReclamationTheme::Fire => "Fire",
+            ReclamationTheme::Tales => "Tales",
+        }
+    }
+}
+
+impl TryFrom<&str> for ReclamationTheme {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        ReclamationTheme::ALL
+            .into_iter()
+            .find(|t| t.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown reclamation theme: {s}"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReclamationMode {
+    /// 刷分与建造点，进入战斗直接退出
+    FarmPoints,
+    /// 刷赤金，联络员买水后基地锻造；沙洲遗闻中为制造物品后读档刷点数
+    FarmCraft,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReclamationIncrementMode {
+    /// 连点
+    Click,
+    /// 长按
+    LongPress,
+}
+
+fn is_zero(v: &usize) -> bool {
+    *v == 0
+}
+
+/// 生息演算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct ReclamationAlgorithm<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+
+    theme: String,
+    mode: usize,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools_to_craft: Vec<String>,
+    increment_mode: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    num_craft_batches: usize,
+}
+
+impl<T: State> Default for ReclamationAlgorithm<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> ReclamationAlgorithm<T> {
+    pub fn new() -> Self {
+        ReclamationAlgorithm {
+            _phantom: PhantomData,
+            id: None,
+            theme: ReclamationTheme::Fire.as_ref().to_string(),
+            mode: 0,
+            tools_to_craft: Vec::new(),
+            increment_mode: 0,
+            num_craft_batches: 0,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl ReclamationAlgorithm<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    /// 主题，可选，默认沙中之火。不支持运行中设置
+    pub fn theme(mut self, theme: ReclamationTheme) -> Self {
+        self.theme = theme.as_ref().to_string();
+        self
+    }
+
+    /// 模式，可选，默认刷分与建造点。不支持运行中设置
+    pub fn mode(mut self, mode: ReclamationMode) -> Self {
+        self.mode = mode as usize;
+        self
+    }
+
+    /// 要制造的物品名，可选，默认荧光棒。仅沙洲遗闻的制造模式有效。不支持运行中设置
+    pub fn tools_to_craft(mut self, tools_to_craft: Vec<&str>) -> Self {
+        self.tools_to_craft = tools_to_craft.into_iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// 点击加号按钮的方式，可选，默认连点。不支持运行中设置
+    pub fn increment_mode(mut self, increment_mode: ReclamationIncrementMode) -> Self {
+        self.increment_mode = increment_mode as usize;
+        self
+    }
+
+    /// 单次最大制造批数，可选，默认 16。不支持运行中设置
+    pub fn num_craft_batches(mut self, num_craft_batches: usize) -> Self {
+        self.num_craft_batches = num_craft_batches;
+        self
+    }
+
+    pub fn start(self) -> ReclamationAlgorithm<Running> {
+        ReclamationAlgorithm {
+            _phantom: PhantomData,
+            id: self.id,
+            theme: self.theme,
+            mode: self.mode,
+            tools_to_craft: self.tools_to_craft,
+            increment_mode: self.increment_mode,
+            num_craft_batches: self.num_craft_batches,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for ReclamationAlgorithm<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "ReclamationAlgorithm"
+    }
+
+    fn check(&self) -> Result<()> {
+        ReclamationTheme::try_from(self.theme.as_str())?;
+        if self.mode > ReclamationMode::FarmCraft as usize {
+            return Err(anyhow!("Unknown reclamation mode: {}", self.mode));
+        }
+        if self.increment_mode > ReclamationIncrementMode::LongPress as usize {
+            return Err(anyhow!(
+                "Unknown reclamation increment mode: {}",
+                self.increment_mode
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 生息演算的进度，即开始执行的子任务
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReclamationStep {
+    pub task: String,
+    pub exec_times: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReclamationReport {
+    pub steps: Vec<ReclamationStep>,
+}
+
+impl ReclamationReport {
+    /// How many times a sub task, such as `Reclamation@ExitLevel`, was started
+    pub fn count(&self, task: &str) -> usize {
+        self.steps.iter().filter(|s| s.task == task).count()
+    }
+}
+
+impl TaskReport for ReclamationReport {
+    fn update(&mut self, _info: &SubTaskExtraInfo) {}
+
+    fn sub_task_start(&mut self, info: &SubTaskStart) {
+        if let Some(task) = &info.details.task {
+            self.steps.push(ReclamationStep {
+                task: task.clone(),
+                exec_times: info.details.exec_times,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let reclamation = ReclamationAlgorithm::new_paused()
+            .theme(ReclamationTheme::Tales)
+            .mode(ReclamationMode::FarmCraft)
+            .tools_to_craft(vec!["荧光棒"])
+            .increment_mode(ReclamationIncrementMode::LongPress)
+            .num_craft_batches(8);
+        assert!(reclamation.check().is_ok());
+        assert_eq!(
+            serde_json::to_value(&reclamation).unwrap(),
+            serde_json::json!({
+                "theme": "Tales",
+                "mode": 1,
+                "tools_to_craft": ["荧光棒"],
+                "increment_mode": 1,
+                "num_craft_batches": 8,
+            })
+        );
+
+        let json = serde_json::to_value(ReclamationAlgorithm::new_paused()).unwrap();
+        assert!(json.get("tools_to_craft").is_none());
+        assert!(json.get("num_craft_batches").is_none());
+
+        let unknown: ReclamationAlgorithm<Paused> =
+            serde_json::from_value(serde_json::json!({ "theme": "Water" })).unwrap();
+        assert!(unknown.check().is_err());
+        let unknown: ReclamationAlgorithm<Paused> =
+            serde_json::from_value(serde_json::json!({ "mode": 2 })).unwrap();
+        assert!(unknown.check().is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let start = |task: Option<&str>, exec_times: i64| -> SubTaskStart {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "ProcessTask", "taskchain": "Reclamation",
+                "taskid": 1, "uuid": "",
+                "details": { "task": task, "exec_times": exec_times }
+            }))
+            .unwrap()
+        };
+        let mut report = ReclamationReport::default();
+        report.sub_task_start(&start(Some("Reclamation@ExitLevel"), 1));
+        report.sub_task_start(&start(None, 1));
+        report.sub_task_start(&start(Some("Reclamation@Begin"), 1));
+        report.sub_task_start(&start(Some("Reclamation@ExitLevel"), 2));
+
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[2].exec_times, Some(2));
+        assert_eq!(report.count("Reclamation@ExitLevel"), 2);
+        assert_eq!(report.count("Reclamation@Craft"), 0);
+    }
+}
@@ -1,18 +1,21 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::binding::tasks::{Paused, Running, Server, State, StoppedTask};
+use crate::binding::events::{SubTaskExtraInfo, SubTaskStart};
+use crate::binding::tasks::{Paused, Running, Server, State, StoppedTask, TaskReport};
+use crate::history::{unix_time, History, Stamped};
 
 fn is_zero(v: &usize) -> bool {
     *v == 0
 }
 
 /// 公开招募
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct Recruit<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -43,6 +46,12 @@ pub struct Recruit<T: State> {
     server: String,
 }
 
+impl<T: State> Default for Recruit<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: State> Recruit<T> {
     pub fn new() -> Self {
         Recruit {
@@ -143,6 +152,14 @@ impl<T: State> Recruit<T> {
         self.server = server.as_ref().to_string();
         self
     }
+
+    /// The recruitment time set for `level` tags, in minutes
+    pub fn recruitment_minutes(&self, level: i64) -> usize {
+        self.recruitment_time
+            .get(&level.to_string())
+            .copied()
+            .unwrap_or(540)
+    }
 }
 
 impl Recruit<Paused> {
@@ -180,4 +197,278 @@ impl<'a> StoppedTask<'a> for Recruit<Paused> {
     fn name(&self) -> &'static str {
         "Recruit"
     }
+
+    fn check(&self) -> Result<()> {
+        Server::try_from(self.server.as_str())?;
+        if let Some(level) = self
+            .select
+            .iter()
+            .chain(&self.confirm)
+            .find(|l| !(1..=6).contains(*l))
+        {
+            return Err(anyhow!("Invalid recruit tag level: {level}"));
+        }
+        for (level, minutes) in &self.recruitment_time {
+            if !["3", "4", "5", "6"].contains(&level.as_str()) {
+                return Err(anyhow!("Invalid recruitment time level: {level}"));
+            }
+            if !(60..=540).contains(minutes) || minutes % 10 != 0 {
+                return Err(anyhow!(
+                    "Invalid recruitment time of level {level}: {minutes} minutes"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 一次公开招募
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecruitRecord {
+    /// 识别到的 Tags
+    pub tags: Vec<String>,
+    /// 选择的 Tags
+    pub selected: Vec<String>,
+    pub level: i64,
+    /// 招募时限，分钟
+    pub minutes: usize,
+    pub expedited: bool,
+    /// 所在的招募位，0 到 3
+    pub slot: usize,
+}
+
+/// When each of the four recruitment slots finishes, as unix seconds.
+///
+/// MaaCore does not report which slot it used, so a new recruitment is put in the first free
+/// slot, the way the task walks through them.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecruitSlots {
+    pub finish: [u64; 4],
+}
+
+impl RecruitSlots {
+    const HISTORY_NAME: &'static str = "recruit_slots";
+
+    pub fn load(history: &History) -> Result<Self> {
+        Ok(history.load(Self::HISTORY_NAME)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, history: &History) -> Result<()> {
+        history.save(Self::HISTORY_NAME, self)
+    }
+
+    pub fn free(&self, now: u64) -> usize {
+        self.finish.iter().filter(|f| **f <= now).count()
+    }
+
+    /// When a `Recruit` run is next useful: now if a slot is free, else when the first one finishes
+    pub fn next_useful(&self, now: u64) -> u64 {
+        self.finish
+            .iter()
+            .min()
+            .copied()
+            .unwrap_or_default()
+            .max(now)
+    }
+
+    /// Start a recruitment of `minutes` at `now`, returns its slot
+    pub fn start(&mut self, now: u64, minutes: usize) -> usize {
+        let slot = self.first_free(now);
+        self.start_in(slot, now, minutes);
+        slot
+    }
+
+    /// Start a recruitment of `minutes` at `now` in `slot`
+    pub fn start_in(&mut self, slot: usize, now: u64, minutes: usize) {
+        if let Some(finish) = self.finish.get_mut(slot) {
+            *finish = now + minutes as u64 * 60;
+        }
+    }
+
+    fn first_free(&self, now: u64) -> usize {
+        match self.finish.iter().position(|f| *f <= now) {
+            Some(slot) => slot,
+            // Slots were freed in the game, the saved times are stale
+            None => (0..4).min_by_key(|i| self.finish[*i]).unwrap_or_default(),
+        }
+    }
+
+    /// Finish the busy slot that finishes first, the one the task expedites once no slot is
+    /// free, returns it
+    pub fn expedite(&mut self, now: u64) -> Option<usize> {
+        let slot = (0..4)
+            .filter(|i| self.finish[*i] > now)
+            .min_by_key(|i| self.finish[*i])?;
+        self.finish[slot] = now;
+        Some(slot)
+    }
+}
+
+/// 公开招募的结果，取自 `RecruitTagsDetected`、`RecruitResult`、`RecruitTagsSelected` 及
+/// `RecruitSlotCompleted` 信息
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecruitReport {
+    pub records: Vec<RecruitRecord>,
+    pub slots: RecruitSlots,
+
+    minutes: HashMap<i64, usize>,
+    #[serde(skip)]
+    pending: RecruitRecord,
+    /// The slot freed by expediting, where the next recruitment goes
+    #[serde(skip)]
+    expedited: Option<usize>,
+}
+
+impl RecruitReport {
+    const HISTORY_NAME: &'static str = "recruit";
+
+    /// A report for `recruit`, starting from the slots saved by the last run
+    pub fn new<T: State>(recruit: &Recruit<T>, slots: RecruitSlots) -> Self {
+        RecruitReport {
+            slots,
+            minutes: (3..=6)
+                .map(|level| (level, recruit.recruitment_minutes(level)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn complete(&mut self, now: u64) {
+        let mut record = std::mem::take(&mut self.pending);
+        record.minutes = self.minutes.get(&record.level).copied().unwrap_or(540);
+        record.slot = match self.expedited.take() {
+            Some(slot) => {
+                record.expedited = true;
+                self.slots.start_in(slot, now, record.minutes);
+                slot
+            }
+            None => self.slots.start(now, record.minutes),
+        };
+        self.records.push(record);
+    }
+
+    /// The task only expedites when no slot is free, so it is usually one started by an
+    /// earlier run. Saved times that show a free slot are stale, the first one is used then.
+    fn expedite(&mut self, now: u64) {
+        let slot = self
+            .slots
+            .expedite(now)
+            .unwrap_or_else(|| self.slots.first_free(now));
+        self.expedited = Some(slot);
+    }
+
+    /// Append the records and save the slots
+    pub fn save(&self, history: &History) -> Result<()> {
+        for record in &self.records {
+            history.append(Self::HISTORY_NAME, &Stamped::now(record))?;
+        }
+        self.slots.save(history)
+    }
+
+    pub fn records(history: &History) -> Result<Vec<Stamped<RecruitRecord>>> {
+        history.records(Self::HISTORY_NAME)
+    }
+}
+
+impl TaskReport for RecruitReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        let details = &info.details;
+        match info.what.as_str() {
+            "RecruitTagsDetected" => {
+                self.pending = RecruitRecord {
+                    tags: details.tags.clone(),
+                    ..Default::default()
+                }
+            }
+            "RecruitResult" => self.pending.level = details.level.unwrap_or_default(),
+            "RecruitTagsSelected" => self.pending.selected = details.tags.clone(),
+            "RecruitSlotCompleted" => self.complete(unix_time()),
+            _ => {}
+        }
+    }
+
+    fn sub_task_start(&mut self, info: &SubTaskStart) {
+        if info.details.task.as_deref() == Some("RecruitNowConfirm") {
+            self.expedite(unix_time());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slots() {
+        let mut slots = RecruitSlots::default();
+        assert_eq!(slots.free(100), 4);
+        assert_eq!(slots.start(100, 540), 0);
+        assert_eq!(slots.start(100, 60), 1);
+        assert_eq!(slots.start(100, 60), 2);
+        assert_eq!(slots.next_useful(100), 100);
+        assert_eq!(slots.start(100, 60), 3);
+        assert_eq!(slots.free(100), 0);
+        assert_eq!(slots.next_useful(100), 3700);
+        assert_eq!(slots.expedite(200), Some(1));
+        assert_eq!(slots.next_useful(100), 200);
+        assert_eq!(slots.start(300, 540), 1);
+        assert_eq!(slots.free(3700), 2);
+        assert_eq!(RecruitSlots::default().expedite(0), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let info = |what: &str, details: serde_json::Value| -> SubTaskExtraInfo {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Recruit", "taskid": 1,
+                "uuid": "", "what": what, "details": details
+            }))
+            .unwrap()
+        };
+        let start = |task: &str| -> SubTaskStart {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Recruit", "taskid": 1,
+                "uuid": "", "details": { "task": task }
+            }))
+            .unwrap()
+        };
+        let recruitment = |report: &mut RecruitReport, level: i64| {
+            let tags = serde_json::json!({ "tags": ["控场", "近战位"] });
+            report.update(&info("RecruitTagsDetected", tags.clone()));
+            report.update(&info(
+                "RecruitResult",
+                serde_json::json!({ "level": level }),
+            ));
+            report.update(&info("RecruitTagsSelected", tags));
+            report.update(&info("RecruitSlotCompleted", serde_json::json!({})));
+        };
+
+        // Every slot is busy from the last run, slot 2 finishing first
+        let now = unix_time();
+        let slots = RecruitSlots {
+            finish: [now + 3000, now + 5000, now + 1000, now + 2000],
+        };
+        let recruit = Recruit::new_paused().recruitment_time(540, 460, 540, 540);
+        let mut report = RecruitReport::new(&recruit, slots);
+        report.sub_task_start(&start("RecruitNowConfirm"));
+        recruitment(&mut report, 4);
+        report.sub_task_start(&start("RecruitNowConfirm"));
+        recruitment(&mut report, 3);
+
+        assert_eq!(report.records.len(), 2);
+        assert_eq!(report.records[0].slot, 2);
+        assert_eq!(report.records[0].minutes, 460);
+        assert!(report.records[0].expedited);
+        assert_eq!(report.records[0].selected, ["控场", "近战位"]);
+        // The next one finishing first is slot 3, slot 2 now takes 460 minutes
+        assert_eq!(report.records[1].slot, 3);
+        assert!(report.records[1].expedited);
+        assert_eq!(report.slots.free(now + 2999), 0);
+
+        // Without expediting, recruitments go to the free slots
+        let mut report = RecruitReport::new(&recruit, RecruitSlots::default());
+        recruitment(&mut report, 3);
+        assert_eq!(report.records[0].slot, 0);
+        assert!(!report.records[0].expedited);
+    }
 }
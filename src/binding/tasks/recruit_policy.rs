@@ -0,0 +1,560 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::{AsstMsg, Events, SubTaskExtraInfo};
+use crate::binding::recruitment::{RecruitCalculator, RecruitCombination};
+use crate::binding::tasks::{Paused, Recruit};
+use crate::history::{History, Stamped};
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecruitAction {
+    /// 招募匹配的组合
+    #[default]
+    Keep,
+    /// 刷新 Tags，仅在 Tags 最高保底三星时可刷新，否则视为跳过
+    Refresh,
+    /// 跳过该栏位
+    Skip,
+}
+
+/// 公招规则，匹配同时满足所有已设条件的 Tag 组合
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct RecruitRule {
+    /// 组合包含的 Tag，中文或客户端显示的名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// 组合可能招到的干员，名称或 id，低于保底星级的干员不算
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// 组合保底的最低星级
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_level: Option<i64>,
+    pub action: RecruitAction,
+}
+
+impl RecruitRule {
+    pub fn new(action: RecruitAction) -> Self {
+        RecruitRule {
+            action,
+            ..Default::default()
+        }
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn operator(mut self, operator: &str) -> Self {
+        self.operator = Some(operator.to_string());
+        self
+    }
+
+    pub fn min_level(mut self, min_level: i64) -> Self {
+        self.min_level = Some(min_level);
+        self
+    }
+
+    /// Check that the tag and operator are known to `calculator`
+    pub fn validate(&self, calculator: &RecruitCalculator) -> Result<()> {
+        if let Some(tag) = &self.tag {
+            calculator.resolve_tag(tag)?;
+        }
+        if let Some(name) = &self.operator {
+            let name = name.to_lowercase();
+            if !calculator
+                .operators()
+                .iter()
+                .any(|o| o.id == name || o.name.to_lowercase() == name)
+            {
+                return Err(anyhow!("Unknown recruit operator: {name}"));
+            }
+        }
+        if let Some(level) = self.min_level {
+            if !(1..=6).contains(&level) {
+                return Err(anyhow!("Invalid recruit level: {level}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, calculator: &RecruitCalculator, combination: &RecruitCombination) -> bool {
+        if let Some(tag) = &self.tag {
+            let Ok(tag) = calculator.resolve_tag(tag) else {
+                return false;
+            };
+            if !combination.tags.iter().any(|t| t == tag) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.operator {
+            let name = name.to_lowercase();
+            let found = combination
+                .operators
+                .iter()
+                .filter(|o| o.rarity >= combination.level)
+                .any(|o| o.id == name || o.name.to_lowercase() == name);
+            if !found {
+                return false;
+            }
+        }
+        self.min_level.unwrap_or(0) <= combination.level
+    }
+}
+
+/// What the policy chose for one recruitment slot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum RecruitDecision {
+    Keep {
+        tags: Vec<String>,
+        level: i64,
+    },
+    Refresh,
+    Skip,
+    /// No rule matched, the task decides by its own star levels
+    Default,
+}
+
+/// A decision and why it was made, as stored in history.
+///
+/// The decision is made on the tags of one slot but only reaches the task for the next
+/// slots, see [`RecruitPolicy`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecruitDecisionRecord {
+    pub task_id: usize,
+    /// The tags shown on the slot, as reported by the task. Not `tags`, which a kept
+    /// decision has
+    pub shown: Vec<String>,
+    /// The decision sent before these tags were shown, the one the task most likely applied
+    /// to them. `None` for the first slot, which the task handles with its own params
+    pub previous: Option<RecruitDecision>,
+    /// Index of the matched rule
+    pub rule: Option<usize>,
+    #[serde(flatten)]
+    pub decision: RecruitDecision,
+}
+
+/// User rules for the recruit tags, the first rule matching a combination of the shown tags
+/// decides, and the decision is sent to the running `Recruit` task.
+///
+/// The core does not wait for the decision: it selects and confirms a slot while the tags
+/// event is still on its way, so a decision made on the tags of one slot is applied from the
+/// next slot on, and the decision on the last slot is never applied. Rules are therefore best
+/// suited to tags that keep showing up, such as refreshing until a wanted tag appears.
+///
+/// ```toml
+/// # always keep Top Operator
+/// [[rules]]
+/// tag = "高级资深干员"
+///
+/// # keep the Robot tag for Lancet-2
+/// [[rules]]
+/// operator = "Lancet-2"
+///
+/// # refresh unless a 4 star combination can give Texas
+/// [[rules]]
+/// operator = "德克萨斯"
+/// min_level = 4
+///
+/// [[rules]]
+/// action = "refresh"
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecruitPolicy {
+    pub rules: Vec<RecruitRule>,
+}
+
+impl RecruitPolicy {
+    const HISTORY_NAME: &'static str = "recruit_policy";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rule(mut self, rule: RecruitRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Check every rule against `calculator`, a rule that can't match is an error
+    pub fn validate(&self, calculator: &RecruitCalculator) -> Result<()> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            rule.validate(calculator)
+                .map_err(|e| anyhow!("Invalid recruit rule {index}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// The decision for the shown `tags`, with the index of the rule that made it.
+    ///
+    /// The core only refreshes tags that guarantee no more than 3 stars, a refresh rule is a
+    /// skip for better tags.
+    pub fn evaluate(
+        &self,
+        calculator: &RecruitCalculator,
+        tags: &[&str],
+    ) -> Result<(Option<usize>, RecruitDecision)> {
+        let combinations = calculator.combinations(tags)?;
+        for (index, rule) in self.rules.iter().enumerate() {
+            // Combinations are sorted best first
+            let Some(combination) = combinations.iter().find(|c| rule.matches(calculator, c))
+            else {
+                continue;
+            };
+            let decision = match rule.action {
+                RecruitAction::Keep => RecruitDecision::Keep {
+                    tags: combination.tags.clone(),
+                    level: combination.level,
+                },
+                RecruitAction::Refresh if combinations[0].level > 3 => {
+                    warn!(
+                        "Recruit rule {index} refreshes {} star tags, skipping instead",
+                        combinations[0].level
+                    );
+                    RecruitDecision::Skip
+                }
+                RecruitAction::Refresh => RecruitDecision::Refresh,
+                RecruitAction::Skip => RecruitDecision::Skip,
+            };
+            return Ok((Some(index), decision));
+        }
+        Ok((None, RecruitDecision::Default))
+    }
+
+    /// `base` changed to carry out `decision`. The core picks the combination of the kept
+    /// level by itself, so it may not be the exact tags of the decision.
+    pub fn apply(base: &Recruit<Paused>, decision: &RecruitDecision) -> Recruit<Paused> {
+        let task = base.clone();
+        match decision {
+            RecruitDecision::Keep { level, .. } => {
+                let level = *level as usize;
+                task.select(vec![level])
+                    .confirm(vec![level])
+                    .skip_robot(level > 1)
+            }
+            RecruitDecision::Refresh => task.refresh(true).select(vec![]).confirm(vec![]),
+            RecruitDecision::Skip => task.refresh(false).select(vec![]).confirm(vec![]),
+            RecruitDecision::Default => task,
+        }
+    }
+
+    pub fn records(history: &History) -> Result<Vec<Stamped<RecruitDecisionRecord>>> {
+        history.records(Self::HISTORY_NAME)
+    }
+
+    /// Decide on every slot of the recruit task `task_id`, appended as `base`, until it ends.
+    ///
+    /// The rules are validated first. Decisions are logged, and appended to `history` if given.
+    /// Each decision reaches the task one slot late, see [`RecruitPolicy`]. Missing events
+    /// is an error, as a missed slot would shift the recorded decisions.
+    pub async fn follow(
+        &self,
+        maa: &MAAConnection,
+        mut events: broadcast::Receiver<Events>,
+        calculator: &RecruitCalculator,
+        base: &Recruit<Paused>,
+        task_id: usize,
+        history: Option<&History>,
+    ) -> Result<Vec<RecruitDecisionRecord>> {
+        let send = |params: &Recruit<Paused>| maa.set_task_params(task_id, params);
+        self.follow_with(send, &mut events, calculator, base, task_id, history)
+            .await
+    }
+
+    /// Same as `follow`, sending the params of each decision through `send`. `events` is
+    /// borrowed so that the next recruit tasks can be followed with it.
+    pub(crate) async fn follow_with(
+        &self,
+        mut send: impl FnMut(&Recruit<Paused>) -> Result<()>,
+        events: &mut broadcast::Receiver<Events>,
+        calculator: &RecruitCalculator,
+        base: &Recruit<Paused>,
+        task_id: usize,
+        history: Option<&History>,
+    ) -> Result<Vec<RecruitDecisionRecord>> {
+        self.validate(calculator)?;
+        let mut records = Vec::new();
+        let mut previous = None;
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    return Err(anyhow!(
+                        "Recruit policy missed {n} events of task {task_id}"
+                    ))
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(anyhow!("Connection closed before task {task_id} ended"))
+                }
+            };
+            if event.params["taskid"].as_u64() != Some(task_id as u64) {
+                continue;
+            }
+            match event.type_ {
+                AsstMsg::SubTaskExtraInfo => {
+                    let info: SubTaskExtraInfo = serde_json::from_value(event.params)?;
+                    if info.what != "RecruitTagsDetected" {
+                        continue;
+                    }
+                    let tags = info.details.tags;
+                    let shown = tags.iter().map(String::as_str).collect::<Vec<_>>();
+                    let (rule, decision) = match self.evaluate(calculator, &shown) {
+                        Ok(evaluated) => evaluated,
+                        Err(e) => {
+                            warn!("Recruit policy failed on [{}]: {e}", shown.join(", "));
+                            (None, RecruitDecision::Default)
+                        }
+                    };
+                    info!(
+                        "Recruit tags [{}]: {:?} by rule {:?}",
+                        shown.join(", "),
+                        decision,
+                        rule
+                    );
+                    send(&Self::apply(base, &decision))?;
+                    let record = RecruitDecisionRecord {
+                        task_id,
+                        shown: tags,
+                        previous: previous.replace(decision.clone()),
+                        rule,
+                        decision,
+                    };
+                    if let Some(history) = history {
+                        history.append(Self::HISTORY_NAME, &Stamped::now(&record))?;
+                    }
+                    records.push(record);
+                }
+                AsstMsg::TaskChainCompleted
+                | AsstMsg::TaskChainError
+                | AsstMsg::TaskChainStopped => return Ok(records),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::recruitment::{RecruitOperator, TOP_OPERATOR};
+    use std::collections::HashMap;
+
+    fn calculator() -> RecruitCalculator {
+        let operator = |name: &str, rarity, tags: &[&str]| RecruitOperator {
+            id: name.to_lowercase(),
+            name: name.to_string(),
+            rarity,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        RecruitCalculator::new(
+            vec![
+                operator("Lancet-2", 1, &["医疗干员", "远程位", "支援机械"]),
+                operator("Ansel", 3, &["医疗干员", "远程位"]),
+                operator("Texas", 4, &["先锋干员", "近战位", "控场"]),
+                operator("Myrrh", 4, &["医疗干员", "远程位"]),
+                operator("Shining", 6, &["医疗干员", "远程位"]),
+            ],
+            HashMap::from([("支援机械".to_string(), "Robot".to_string())]),
+        )
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let calculator = calculator();
+        let policy: RecruitPolicy = toml::from_str(
+            r#"
+            [[rules]]
+            tag = "高级资深干员"
+
+            [[rules]]
+            operator = "Lancet-2"
+
+            [[rules]]
+            operator = "texas"
+            min_level = 4
+
+            [[rules]]
+            action = "refresh"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.rules[3], RecruitRule::new(RecruitAction::Refresh));
+        assert!(policy.validate(&calculator).is_ok());
+
+        let (rule, decision) = policy
+            .evaluate(&calculator, &["医疗干员", TOP_OPERATOR])
+            .unwrap();
+        assert_eq!(rule, Some(0));
+        assert_eq!(
+            decision,
+            RecruitDecision::Keep {
+                tags: vec![TOP_OPERATOR.to_string()],
+                level: 6
+            }
+        );
+        let (rule, decision) = policy.evaluate(&calculator, &["Robot", "远程位"]).unwrap();
+        assert_eq!(rule, Some(1));
+        let base = Recruit::new_paused();
+        assert_eq!(
+            RecruitPolicy::apply(&base, &decision),
+            base.clone()
+                .select(vec![1])
+                .confirm(vec![1])
+                .skip_robot(false)
+        );
+        assert_eq!(
+            policy.evaluate(&calculator, &["控场"]).unwrap(),
+            (
+                Some(2),
+                RecruitDecision::Keep {
+                    tags: vec!["控场".to_string()],
+                    level: 4
+                }
+            )
+        );
+        assert_eq!(
+            policy.evaluate(&calculator, &["医疗干员"]).unwrap(),
+            (Some(3), RecruitDecision::Refresh)
+        );
+        assert_eq!(
+            RecruitPolicy::new()
+                .evaluate(&calculator, &["医疗干员"])
+                .unwrap(),
+            (None, RecruitDecision::Default)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let calculator = calculator();
+        let typo = RecruitPolicy::new().rule(RecruitRule::new(RecruitAction::Keep).tag("Robto"));
+        assert!(typo.validate(&calculator).is_err());
+        let typo =
+            RecruitPolicy::new().rule(RecruitRule::new(RecruitAction::Keep).operator("Texsa"));
+        assert!(typo.validate(&calculator).is_err());
+        let level = RecruitPolicy::new().rule(RecruitRule::new(RecruitAction::Keep).min_level(7));
+        assert!(level.validate(&calculator).is_err());
+
+        // Only 3 star tags can be refreshed
+        let refresh = RecruitPolicy::new().rule(RecruitRule::new(RecruitAction::Refresh));
+        assert_eq!(
+            refresh.evaluate(&calculator, &["控场"]).unwrap(),
+            (Some(0), RecruitDecision::Skip)
+        );
+        assert_eq!(
+            refresh.evaluate(&calculator, &["远程位"]).unwrap(),
+            (Some(0), RecruitDecision::Refresh)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_follow() {
+        let calculator = calculator();
+        let policy = RecruitPolicy::new()
+            .rule(RecruitRule::new(RecruitAction::Keep).tag("Robot"))
+            .rule(RecruitRule::new(RecruitAction::Refresh));
+        let event = |type_, task_id: usize, what: &str, tags: &[&str]| Events {
+            type_,
+            params: serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Recruit", "taskid": task_id,
+                "uuid": "", "what": what, "details": { "tags": tags }
+            }),
+        };
+        let (sender, mut events) = broadcast::channel(16);
+        for e in [
+            event(
+                AsstMsg::SubTaskExtraInfo,
+                1,
+                "RecruitTagsDetected",
+                &["支援机械", "远程位"],
+            ),
+            event(
+                AsstMsg::SubTaskExtraInfo,
+                2,
+                "RecruitTagsDetected",
+                &["控场"],
+            ),
+            event(AsstMsg::SubTaskExtraInfo, 1, "RecruitResult", &[]),
+            event(
+                AsstMsg::SubTaskExtraInfo,
+                1,
+                "RecruitTagsDetected",
+                &["医疗干员"],
+            ),
+            event(AsstMsg::TaskChainCompleted, 1, "", &[]),
+        ] {
+            sender.send(e).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open(dir.path(), "main").unwrap();
+        let base = Recruit::new_paused();
+        let mut sent = Vec::new();
+        let records = policy
+            .follow_with(
+                |params| {
+                    sent.push(params.clone());
+                    Ok(())
+                },
+                &mut events,
+                &calculator,
+                &base,
+                1,
+                Some(&history),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rule, Some(0));
+        assert_eq!(records[0].previous, None);
+        assert_eq!(records[1].decision, RecruitDecision::Refresh);
+        // The medic tags were confirmed with the decision on the robot tags
+        assert_eq!(records[1].previous, Some(records[0].decision.clone()));
+        assert_eq!(
+            sent,
+            [
+                RecruitPolicy::apply(&base, &records[0].decision),
+                RecruitPolicy::apply(&base, &RecruitDecision::Refresh)
+            ]
+        );
+        let saved = RecruitPolicy::records(&history).unwrap();
+        assert_eq!(
+            saved.into_iter().map(|r| r.value).collect::<Vec<_>>(),
+            records
+        );
+
+        // Missed events are an error, the records would be shifted
+        let (sender, mut events) = broadcast::channel(1);
+        for tags in [&["支援机械"][..], &["控场"][..]] {
+            sender
+                .send(event(
+                    AsstMsg::SubTaskExtraInfo,
+                    1,
+                    "RecruitTagsDetected",
+                    tags,
+                ))
+                .unwrap();
+        }
+        assert!(policy
+            .follow_with(|_| Ok(()), &mut events, &calculator, &base, 1, None)
+            .await
+            .is_err());
+
+        // A rule that can never match is refused before following
+        let (_sender, mut events) = broadcast::channel(16);
+        let typo = RecruitPolicy::new().rule(RecruitRule::new(RecruitAction::Keep).tag("Robto"));
+        assert!(typo
+            .follow_with(|_| Ok(()), &mut events, &calculator, &base, 1, None)
+            .await
+            .is_err());
+    }
+}
@@ -1,15 +1,39 @@
 use std::marker::PhantomData;
+use std::path::Path;
 
+use anyhow::{anyhow, Result};
+use log::{info, warn};
 use serde::Deserialize;
 use serde::Serialize;
+use tokio::sync::broadcast;
 
-use crate::binding::tasks::{Paused, Running, State, StoppedTask};
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::{Events, SubTaskExtraInfo, SubTaskStart};
+use crate::binding::resources::{load_characters, load_ocr_replaces, CharacterMap};
+use crate::binding::tasks::{
+    apply_event, ClientType, Paused, Running, State, StoppedTask, TaskReport,
+};
+use crate::history::{unix_time, History};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RogueLikeTheme {
     /// 傀影与猩红血钻
     Phantom,
     /// 水月与深蓝之树
-    Mizuki
+    Mizuki,
+    /// 探索者的银凇止境
+    Sami,
+    /// 萨卡兹的无终奇语
+    Sarkaz,
+}
+
+impl RogueLikeTheme {
+    pub const ALL: [RogueLikeTheme; 4] = [
+        RogueLikeTheme::Phantom,
+        RogueLikeTheme::Mizuki,
+        RogueLikeTheme::Sami,
+        RogueLikeTheme::Sarkaz,
+    ];
 }
 
 impl AsRef<str> for RogueLikeTheme {
@@ -17,21 +41,56 @@ impl AsRef<str> for RogueLikeTheme {
         match self {
             RogueLikeTheme::Phantom => "Phantom",
             RogueLikeTheme::Mizuki => "Mizuki",
+            RogueLikeTheme::Sami => "Sami",
+            RogueLikeTheme::Sarkaz => "Sarkaz",
         }
     }
 }
 
+impl TryFrom<&str> for RogueLikeTheme {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        RogueLikeTheme::ALL
+            .into_iter()
+            .find(|t| t.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown roguelike theme: {s}"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RogueLikeMode {
     /// 刷积分，尽可能稳定的打更多层数
-    MostFloors,
+    MostFloors = 0,
     /// 刷源石锭，第一层投资完就退出
-    FarmMoney,
+    FarmMoney = 1,
+    /// 刷开局，以获得热水壶或精二干员等开局
+    Collectible = 4,
+    /// 刷坍缩范式，仅萨米肉鸽
+    CollapsalParadigms = 5,
+}
+
+impl RogueLikeMode {
+    pub const ALL: [RogueLikeMode; 4] = [
+        RogueLikeMode::MostFloors,
+        RogueLikeMode::FarmMoney,
+        RogueLikeMode::Collectible,
+        RogueLikeMode::CollapsalParadigms,
+    ];
+
+    pub fn is_available_in(&self, theme: RogueLikeTheme) -> bool {
+        match self {
+            RogueLikeMode::CollapsalParadigms => theme == RogueLikeTheme::Sami,
+            _ => true,
+        }
+    }
 }
 
 fn is_zero(v: &usize) -> bool {
     *v == 0
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RogueLikeSquad {
     /// 指挥分队
     Leader,
@@ -49,16 +108,89 @@ pub enum RogueLikeSquad {
     TacticalRanged,
     /// 破坏战术分队
     TacticalDestruction,
-    /// 研究分队
+    /// 研究分队，仅傀影、水月肉鸽
     Research,
     /// 高规格分队
     FirstClass,
-    /// 心胜于物分队
+    /// 心胜于物分队，仅水月肉鸽
     MindOverMatter,
-    /// 物尽其用分队
+    /// 物尽其用分队，仅水月肉鸽
     Resourceful,
-    /// 以人为本分队
+    /// 以人为本分队，仅水月肉鸽
     PeopleOriented,
+    /// 永恒狩猎分队，仅萨米肉鸽
+    EternalHunting,
+    /// 生活至上分队，仅萨米肉鸽
+    LifeFirst,
+    /// 科学主义分队，仅萨米肉鸽
+    Scientism,
+    /// 特训分队，仅萨米肉鸽
+    SpecialTraining,
+    /// 魂灵护送分队，仅萨卡兹肉鸽
+    SoulEscort,
+    /// 博闻广记分队，仅萨卡兹肉鸽
+    Erudite,
+    /// 蓝图测绘分队，仅萨卡兹肉鸽
+    Blueprint,
+    /// 因地制宜分队，仅萨卡兹肉鸽
+    Adaptive,
+    /// 点刺成锭分队，仅萨卡兹肉鸽
+    IngotPiercing,
+    /// 拟态学者分队，仅萨卡兹肉鸽
+    Mimicry,
+    /// 异想天开分队，仅萨卡兹肉鸽
+    Fanciful,
+}
+
+impl RogueLikeSquad {
+    pub const ALL: [RogueLikeSquad; 24] = [
+        RogueLikeSquad::Leader,
+        RogueLikeSquad::Gathering,
+        RogueLikeSquad::Support,
+        RogueLikeSquad::Spearhead,
+        RogueLikeSquad::TacticalAssault,
+        RogueLikeSquad::TacticalFortification,
+        RogueLikeSquad::TacticalRanged,
+        RogueLikeSquad::TacticalDestruction,
+        RogueLikeSquad::Research,
+        RogueLikeSquad::FirstClass,
+        RogueLikeSquad::MindOverMatter,
+        RogueLikeSquad::Resourceful,
+        RogueLikeSquad::PeopleOriented,
+        RogueLikeSquad::EternalHunting,
+        RogueLikeSquad::LifeFirst,
+        RogueLikeSquad::Scientism,
+        RogueLikeSquad::SpecialTraining,
+        RogueLikeSquad::SoulEscort,
+        RogueLikeSquad::Erudite,
+        RogueLikeSquad::Blueprint,
+        RogueLikeSquad::Adaptive,
+        RogueLikeSquad::IngotPiercing,
+        RogueLikeSquad::Mimicry,
+        RogueLikeSquad::Fanciful,
+    ];
+
+    pub fn is_available_in(&self, theme: RogueLikeTheme) -> bool {
+        use RogueLikeTheme::*;
+        match self {
+            RogueLikeSquad::Research => matches!(theme, Phantom | Mizuki),
+            RogueLikeSquad::MindOverMatter
+            | RogueLikeSquad::Resourceful
+            | RogueLikeSquad::PeopleOriented => theme == Mizuki,
+            RogueLikeSquad::EternalHunting
+            | RogueLikeSquad::LifeFirst
+            | RogueLikeSquad::Scientism
+            | RogueLikeSquad::SpecialTraining => theme == Sami,
+            RogueLikeSquad::SoulEscort
+            | RogueLikeSquad::Erudite
+            | RogueLikeSquad::Blueprint
+            | RogueLikeSquad::Adaptive
+            | RogueLikeSquad::IngotPiercing
+            | RogueLikeSquad::Mimicry
+            | RogueLikeSquad::Fanciful => theme == Sarkaz,
+            _ => true,
+        }
+    }
 }
 
 impl AsRef<str> for RogueLikeSquad {
@@ -77,10 +209,33 @@ impl AsRef<str> for RogueLikeSquad {
             RogueLikeSquad::MindOverMatter => "心胜于物分队",
             RogueLikeSquad::Resourceful => "物尽其用分队",
             RogueLikeSquad::PeopleOriented => "以人为本分队",
+            RogueLikeSquad::EternalHunting => "永恒狩猎分队",
+            RogueLikeSquad::LifeFirst => "生活至上分队",
+            RogueLikeSquad::Scientism => "科学主义分队",
+            RogueLikeSquad::SpecialTraining => "特训分队",
+            RogueLikeSquad::SoulEscort => "魂灵护送分队",
+            RogueLikeSquad::Erudite => "博闻广记分队",
+            RogueLikeSquad::Blueprint => "蓝图测绘分队",
+            RogueLikeSquad::Adaptive => "因地制宜分队",
+            RogueLikeSquad::IngotPiercing => "点刺成锭分队",
+            RogueLikeSquad::Mimicry => "拟态学者分队",
+            RogueLikeSquad::Fanciful => "异想天开分队",
         }
     }
 }
 
+impl TryFrom<&str> for RogueLikeSquad {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        RogueLikeSquad::ALL
+            .into_iter()
+            .find(|squad| squad.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown roguelike squad: {s}"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RogueLikeRoles {
     /// 随心所欲
     AsYourHeartDesires,
@@ -92,6 +247,15 @@ pub enum RogueLikeRoles {
     OvercomingYourWeakness,
 }
 
+impl RogueLikeRoles {
+    pub const ALL: [RogueLikeRoles; 4] = [
+        RogueLikeRoles::AsYourHeartDesires,
+        RogueLikeRoles::FireMoveAdvance,
+        RogueLikeRoles::SlowAndSteayWinsTheRace,
+        RogueLikeRoles::OvercomingYourWeakness,
+    ];
+}
+
 impl AsRef<str> for RogueLikeRoles {
     fn as_ref(&self) -> &str {
         match self {
@@ -103,9 +267,124 @@ impl AsRef<str> for RogueLikeRoles {
     }
 }
 
+impl TryFrom<&str> for RogueLikeRoles {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        RogueLikeRoles::ALL
+            .into_iter()
+            .find(|roles| roles.as_ref() == s)
+            .ok_or_else(|| anyhow!("Unknown roguelike roles: {s}"))
+    }
+}
+
+/// 按客户端解析分队、职业组及开局干员的名字。
+///
+/// 干员名取自 `battle_data.json`，分队及职业组取自客户端资源中 `ocrReplace` 的识别结果替换
+#[derive(Debug, Clone, PartialEq)]
+pub struct RogueLikeNames {
+    client: ClientType,
+    characters: CharacterMap,
+    replaces: Vec<(String, String)>,
+}
+
+impl RogueLikeNames {
+    pub fn new(
+        client: ClientType,
+        characters: CharacterMap,
+        replaces: Vec<(String, String)>,
+    ) -> Self {
+        RogueLikeNames {
+            client,
+            characters,
+            replaces,
+        }
+    }
+
+    /// Load from `resources_path`, the path given to `MAABuilder`
+    pub fn load<P: AsRef<Path>>(resources_path: P, client: ClientType) -> Result<Self> {
+        let characters = load_characters(resources_path.as_ref().join("resource"))?;
+        let names = Self::load_texts(resources_path, client)?;
+        Ok(Self::new(client, characters, names.replaces))
+    }
+
+    /// Like `load` but without the operators, enough for the squads and roles
+    pub fn load_texts<P: AsRef<Path>>(resources_path: P, client: ClientType) -> Result<Self> {
+        let resource = resources_path.as_ref().join("resource");
+        let replaces = match client.resource_dir() {
+            Some(dir) => load_ocr_replaces(resource.join("global").join(dir).join("resource"))?,
+            None => Vec::new(),
+        };
+        Ok(Self::new(client, CharacterMap::new(), replaces))
+    }
+
+    /// What the core compares the recognized text with, for the Chinese text `zh`.
+    ///
+    /// The client's recognition results are either replaced to `zh`, or `zh` itself is
+    /// replaced to the client's text.
+    fn core_text(&self, zh: &str) -> String {
+        if self.client.resource_dir().is_none() || self.replaces.iter().any(|(_, r)| r == zh) {
+            return zh.to_string();
+        }
+        match self.replaces.iter().find(|(p, _)| p == zh) {
+            Some((_, localized)) => localized.clone(),
+            None => {
+                warn!("No {:?} text for {}, using it as is", self.client, zh);
+                zh.to_string()
+            }
+        }
+    }
+
+    /// The Chinese text recognized as `text` by the client
+    fn zh_text<'a>(&'a self, text: &'a str) -> &'a str {
+        self.replaces
+            .iter()
+            .find(|(p, r)| p == text || r == text)
+            .map_or(
+                text,
+                |(p, r)| if p == text { r.as_str() } else { p.as_str() },
+            )
+    }
+
+    pub fn squad(&self, squad: RogueLikeSquad) -> String {
+        self.core_text(squad.as_ref())
+    }
+
+    pub fn roles(&self, roles: RogueLikeRoles) -> String {
+        self.core_text(roles.as_ref())
+    }
+
+    /// A squad by its name in any client
+    pub fn find_squad(&self, name: &str) -> Option<RogueLikeSquad> {
+        RogueLikeSquad::try_from(name)
+            .or_else(|_| RogueLikeSquad::try_from(self.zh_text(name)))
+            .ok()
+    }
+
+    /// Roles by their name in any client
+    pub fn find_roles(&self, name: &str) -> Option<RogueLikeRoles> {
+        RogueLikeRoles::try_from(name)
+            .or_else(|_| RogueLikeRoles::try_from(self.zh_text(name)))
+            .ok()
+    }
+
+    /// An operator id, or its name in any client, to its name in this client
+    pub fn core_char(&self, id_or_name: &str) -> Result<String> {
+        let character = self
+            .characters
+            .get(id_or_name)
+            .or_else(|| self.characters.values().find(|c| c.is_named(id_or_name)))
+            .ok_or_else(|| anyhow!("Unknown operator: {id_or_name}"))?;
+        character
+            .name_for(self.client)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("{} is not available on {:?}", character.name, self.client))
+    }
+}
+
 /// 无限刷肉鸽
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct RogueLike<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -134,6 +413,12 @@ pub struct RogueLike<T: State> {
     refresh_trader_with_dice: bool,
 }
 
+impl<T: State> Default for RogueLike<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: State> RogueLike<T> {
     pub fn new() -> Self {
         RogueLike {
@@ -202,12 +487,30 @@ impl<T: State> RogueLike<T> {
         self
     }
 
-    /// 开局干员名，可选，仅支持单个干员中！文！名！。默认识别练度自动选择
+    /// 开局干员名，可选，仅支持单个干员，须为客户端所显示的名字。默认识别练度自动选择
     pub fn core_char(mut self, core_char: &str) -> Self {
         self.core_char = core_char.to_string();
         self
     }
 
+    /// 同 `squad`，但使用 `names` 所对应客户端的名字
+    pub fn squad_in(mut self, squad: RogueLikeSquad, names: &RogueLikeNames) -> Self {
+        self.squad = names.squad(squad);
+        self
+    }
+
+    /// 同 `roles`，但使用 `names` 所对应客户端的名字
+    pub fn roles_in(mut self, roles: RogueLikeRoles, names: &RogueLikeNames) -> Self {
+        self.roles = names.roles(roles);
+        self
+    }
+
+    /// 同 `core_char`，但可为干员 ID 或任一客户端的干员名，会转换为 `names` 所对应客户端的名字
+    pub fn core_char_in(mut self, core_char: &str, names: &RogueLikeNames) -> Result<Self> {
+        self.core_char = names.core_char(core_char)?;
+        Ok(self)
+    }
+
     /// 开局干员是否为助战干员，是否可以是非好友助战干员，默认 false
     pub fn use_support(mut self, use_support: bool, use_nonfriend_support: bool) -> Self {
         self.use_support = use_support;
@@ -220,6 +523,56 @@ impl<T: State> RogueLike<T> {
         self.refresh_trader_with_dice = refresh_trader_with_dice;
         self
     }
+
+    /// Check that the theme supports the chosen mode, squad and options.
+    ///
+    /// Squads and roles not named in Chinese are left to `validate_names`
+    pub fn validate_options(&self) -> Result<()> {
+        let theme = RogueLikeTheme::try_from(self.theme.as_str())?;
+        let mode = RogueLikeMode::ALL
+            .into_iter()
+            .find(|m| *m as usize == self.mode)
+            .ok_or_else(|| anyhow!("Unknown roguelike mode: {}", self.mode))?;
+        if !mode.is_available_in(theme) {
+            return Err(anyhow!("Mode {mode:?} is not available in {theme:?}"));
+        }
+        if let Ok(squad) = RogueLikeSquad::try_from(self.squad.as_str()) {
+            if !squad.is_available_in(theme) {
+                return Err(anyhow!(
+                    "Squad {} is not available in {theme:?}",
+                    self.squad
+                ));
+            }
+        }
+        if self.refresh_trader_with_dice && theme != RogueLikeTheme::Mizuki {
+            return Err(anyhow!(
+                "Refreshing the trader with dice is only available in Mizuki"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the squad and roles are names of the client of `names`, or Chinese ones
+    pub fn validate_names(&self, names: &RogueLikeNames) -> Result<()> {
+        let theme = RogueLikeTheme::try_from(self.theme.as_str())?;
+        if !self.squad.is_empty() {
+            let squad = names
+                .find_squad(&self.squad)
+                .ok_or_else(|| anyhow!("Unknown roguelike squad: {}", self.squad))?;
+            if !squad.is_available_in(theme) {
+                return Err(anyhow!(
+                    "Squad {} is not available in {theme:?}",
+                    self.squad
+                ));
+            }
+        }
+        if !self.roles.is_empty() {
+            names
+                .find_roles(&self.roles)
+                .ok_or_else(|| anyhow!("Unknown roguelike roles: {}", self.roles))?;
+        }
+        Ok(())
+    }
 }
 
 impl RogueLike<Paused> {
@@ -255,4 +608,412 @@ impl<'a> StoppedTask<'a> for RogueLike<Paused> {
     fn name(&self) -> &'static str {
         "Roguelike"
     }
+
+    fn check(&self) -> Result<()> {
+        self.validate_options()
+    }
+
+    /// Squads and roles must be known in Chinese or in one of the global clients
+    fn validate(&self, maa: &MAAConnection) -> Result<()> {
+        self.check()?;
+        let chinese = RogueLikeNames::new(ClientType::Official, CharacterMap::new(), Vec::new());
+        let Err(e) = self.validate_names(&chinese) else {
+            return Ok(());
+        };
+        for client in ClientType::ALL {
+            if client.resource_dir().is_none() {
+                continue;
+            }
+            let names = RogueLikeNames::load_texts(maa.resources_path(), client)?;
+            if self.validate_names(&names).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(e)
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RogueLikeOutcome {
+    /// 仍在进行，或任务在结算前停止
+    #[default]
+    Unfinished,
+    /// 通关
+    Passed,
+    /// 战斗失败
+    Failed,
+    /// 主动放弃，如刷源石锭时投资完退出
+    Abandoned,
+}
+
+/// 一次探索
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RogueLikeRun {
+    /// 开始的 unix 时间，秒
+    pub start: u64,
+    pub duration: u64,
+    pub squad: String,
+    pub roles: String,
+    pub core_char: String,
+    /// 结算时到达的层数
+    pub floor: Option<i64>,
+    /// 进入的关卡及节点数
+    pub stages: usize,
+    /// 是否到达了投资系统
+    #[serde(default)]
+    pub investment_reached: bool,
+    pub ingots_invested: i64,
+    pub outcome: RogueLikeOutcome,
+}
+
+/// 肉鸽的探索记录，取自 `RoguelikeInvestment`、`RoguelikeSettlement` 信息及各子任务
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RogueLikeReport {
+    pub runs: Vec<RogueLikeRun>,
+    /// 最近一次投资后的存款
+    pub deposit: Option<i64>,
+    pub investment_full: bool,
+
+    squad: String,
+    roles: String,
+    core_char: String,
+    #[serde(skip)]
+    investment_enabled: bool,
+    /// Runs of `runs` already returned by `take_finished`
+    #[serde(skip)]
+    taken: usize,
+}
+
+impl RogueLikeReport {
+    const HISTORY_NAME: &'static str = "roguelike";
+
+    /// A report whose runs carry the squad, roles and core operator of `rogue`
+    pub fn new<T: State>(rogue: &RogueLike<T>) -> Self {
+        RogueLikeReport {
+            squad: rogue.squad.clone(),
+            roles: rogue.roles.clone(),
+            core_char: rogue.core_char.clone(),
+            investment_enabled: rogue.investment_enabled,
+            ..Default::default()
+        }
+    }
+
+    fn end_run(&mut self, outcome: RogueLikeOutcome) {
+        if let Some(run) = self.runs.last_mut() {
+            if run.outcome == RogueLikeOutcome::Unfinished {
+                run.outcome = outcome;
+                run.duration = unix_time().saturating_sub(run.start);
+            }
+        }
+    }
+
+    /// Runs that ended since the last call
+    pub fn take_finished(&mut self) -> &[RogueLikeRun] {
+        let from = self.taken;
+        let finished = self.runs[from..]
+            .iter()
+            .take_while(|r| r.outcome != RogueLikeOutcome::Unfinished)
+            .count();
+        self.taken += finished;
+        &self.runs[from..self.taken]
+    }
+
+    pub fn save(runs: &[RogueLikeRun], history: &History) -> Result<()> {
+        for run in runs {
+            history.append(Self::HISTORY_NAME, run)?;
+        }
+        Ok(())
+    }
+
+    pub fn records(history: &History) -> Result<Vec<RogueLikeRun>> {
+        history.records(Self::HISTORY_NAME)
+    }
+
+    pub fn ingots_invested(&self) -> i64 {
+        self.runs.iter().map(|r| r.ingots_invested).sum()
+    }
+
+    /// Ingots invested per hour, from the start of the first run to the end of the last one
+    pub fn ingots_per_hour(&self) -> Option<f64> {
+        let first = self.runs.first()?;
+        let last = self.runs.last()?;
+        let secs = (last.start + last.duration).saturating_sub(first.start);
+        if secs == 0 {
+            return None;
+        }
+        Some(self.ingots_invested() as f64 * 3600.0 / secs as f64)
+    }
+
+    /// Whether the last `idle_runs` runs that reached the investment system all invested
+    /// nothing, which happens when the deposit is capped but the trader never says so.
+    ///
+    /// Never when investing is disabled, and runs that ended before investing don't count.
+    pub fn cap_reached(&self, idle_runs: usize) -> bool {
+        if !self.investment_enabled || idle_runs == 0 {
+            return false;
+        }
+        let finished = self
+            .runs
+            .iter()
+            .rev()
+            .filter(|r| r.outcome != RogueLikeOutcome::Unfinished && r.investment_reached)
+            .take(idle_runs)
+            .collect::<Vec<_>>();
+        finished.len() == idle_runs && finished.iter().all(|r| r.ingots_invested == 0)
+    }
+}
+
+impl TaskReport for RogueLikeReport {
+    fn update(&mut self, info: &SubTaskExtraInfo) {
+        let details = &info.details;
+        match info.what.as_str() {
+            "RoguelikeInvestment" => {
+                if let Some(run) = self.runs.last_mut() {
+                    run.ingots_invested += details.count.unwrap_or(0);
+                }
+                self.deposit = details.deposit.or(self.deposit);
+            }
+            "RoguelikeSettlement" => {
+                if let Some(run) = self.runs.last_mut() {
+                    run.floor = details.floor;
+                }
+                self.end_run(if details.game_pass.unwrap_or(false) {
+                    RogueLikeOutcome::Passed
+                } else {
+                    RogueLikeOutcome::Failed
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn sub_task_start(&mut self, info: &SubTaskStart) {
+        let Some(task) = info.details.task.as_deref() else {
+            return;
+        };
+        // Tasks may be prefixed by the theme, e.g. `Sami@Roguelike@StartExplore`
+        let task = task.rsplit('@').next().unwrap_or(task);
+        match task {
+            "StartExplore" => {
+                self.end_run(RogueLikeOutcome::Abandoned);
+                self.runs.push(RogueLikeRun {
+                    start: unix_time(),
+                    squad: self.squad.clone(),
+                    roles: self.roles.clone(),
+                    core_char: self.core_char.clone(),
+                    ..Default::default()
+                });
+            }
+            "ExitThenAbandon" => self.end_run(RogueLikeOutcome::Abandoned),
+            "GamePass" => self.end_run(RogueLikeOutcome::Passed),
+            "MissionFailedFlag" => self.end_run(RogueLikeOutcome::Failed),
+            "StageTraderInvestSystemFull" => self.investment_full = true,
+            _ if task.starts_with("StageTraderInvest") => {
+                if let Some(run) = self.runs.last_mut() {
+                    run.investment_reached = true;
+                }
+            }
+            _ if task.starts_with("Stage") && task.ends_with("Enter") => {
+                if let Some(run) = self.runs.last_mut() {
+                    run.stages += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Follow the roguelike task `task_id`, appending each finished run to `history`, and stop
+/// the connection once `idle_runs` runs in a row invested nothing (0 never stops).
+///
+/// MaaCore can only stop the whole queue, so it is only stopped while the latest event comes
+/// from the roguelike task, and the tasks after it are dropped too.
+pub async fn watch_roguelike(
+    maa: &MAAConnection,
+    mut events: broadcast::Receiver<Events>,
+    task_id: usize,
+    mut report: RogueLikeReport,
+    history: Option<&History>,
+    idle_runs: usize,
+) -> Result<RogueLikeReport> {
+    let mut running = None;
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                return Err(anyhow!(
+                    "Roguelike report missed {n} events of task {task_id}"
+                ))
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(report),
+        };
+        if let Some(id) = event.params["taskid"].as_u64() {
+            running = Some(id);
+        }
+        let ended = apply_event(&mut report, event, task_id)?;
+        let finished = report.take_finished();
+        if let Some(history) = history {
+            RogueLikeReport::save(finished, history)?;
+        }
+        if !ended
+            && !finished.is_empty()
+            && running == Some(task_id as u64)
+            && report.cap_reached(idle_runs)
+        {
+            info!(
+                "No ingots invested in the last {} runs, stopping",
+                idle_runs
+            );
+            maa.stop();
+        }
+        if ended {
+            return Ok(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_options() {
+        let rogue = RogueLike::new_paused().theme(RogueLikeTheme::Sami);
+        assert!(rogue.validate_options().is_ok());
+        assert!(rogue
+            .clone()
+            .squad(RogueLikeSquad::Research)
+            .validate_options()
+            .is_err());
+        assert!(rogue
+            .clone()
+            .squad(RogueLikeSquad::SpecialTraining)
+            .mode(RogueLikeMode::CollapsalParadigms)
+            .validate_options()
+            .is_ok());
+        assert!(rogue
+            .theme(RogueLikeTheme::Sarkaz)
+            .squad(RogueLikeSquad::SpecialTraining)
+            .validate_options()
+            .is_err());
+        assert!(RogueLike::new_paused()
+            .mode(RogueLikeMode::CollapsalParadigms)
+            .validate_options()
+            .is_err());
+    }
+
+    #[test]
+    fn test_names() {
+        use crate::binding::resources::Character;
+
+        let characters = CharacterMap::from([(
+            "char_002_amiya".to_string(),
+            Character {
+                name: "阿米娅".to_string(),
+                name_en: Some("Amiya".to_string()),
+                ..Default::default()
+            },
+        )]);
+        let replaces = vec![("Leader Squad".to_string(), "指挥分队".to_string())];
+        let en = RogueLikeNames::new(ClientType::YoStarEN, characters.clone(), replaces.clone());
+        assert_eq!(en.core_char("阿米娅").unwrap(), "Amiya");
+        assert_eq!(en.core_char("char_002_amiya").unwrap(), "Amiya");
+        assert_eq!(en.core_char("amiya").unwrap(), "Amiya");
+        assert!(en.core_char("Kal'tsit").is_err());
+        assert_eq!(en.squad(RogueLikeSquad::Leader), "指挥分队");
+        assert_eq!(en.find_squad("Leader Squad"), Some(RogueLikeSquad::Leader));
+        assert_eq!(en.find_squad("Leader Sqaud"), None);
+
+        let jp = RogueLikeNames::new(ClientType::YoStarJP, characters, replaces);
+        assert!(jp.core_char("Amiya").is_err());
+    }
+
+    #[test]
+    fn test_validate_names() {
+        let replaces = vec![
+            ("Leader Squad".to_string(), "指挥分队".to_string()),
+            ("Research Squad".to_string(), "研究分队".to_string()),
+            ("Flexible".to_string(), "取长补短".to_string()),
+        ];
+        let en = RogueLikeNames::new(ClientType::YoStarEN, CharacterMap::new(), replaces);
+        let rogue = RogueLike::new_paused().theme(RogueLikeTheme::Sami);
+        assert!(rogue.validate_names(&en).is_ok());
+
+        let mut localized = rogue.clone();
+        localized.squad = "Leader Squad".to_string();
+        localized.roles = "Flexible".to_string();
+        assert!(localized.validate_options().is_ok());
+        assert!(localized.validate_names(&en).is_ok());
+
+        let mut typo = localized.clone();
+        typo.squad = "Leader Sqaud".to_string();
+        assert!(typo.validate_names(&en).is_err());
+        let mut typo = localized.clone();
+        typo.roles = "Flexibel".to_string();
+        assert!(typo.validate_names(&en).is_err());
+
+        // Research is not in Sami, whatever its name
+        localized.squad = "Research Squad".to_string();
+        assert!(localized.validate_names(&en).is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let start = |task: &str| -> SubTaskStart {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Roguelike", "taskid": 1,
+                "uuid": "", "details": { "task": task }
+            }))
+            .unwrap()
+        };
+        let info = |what: &str, details: serde_json::Value| -> SubTaskExtraInfo {
+            serde_json::from_value(serde_json::json!({
+                "class": "", "subtask": "", "taskchain": "Roguelike", "taskid": 1,
+                "uuid": "", "what": what, "details": details
+            }))
+            .unwrap()
+        };
+
+        let rogue = RogueLike::new_paused().squad(RogueLikeSquad::Research);
+        let mut report = RogueLikeReport::new(&rogue);
+        report.sub_task_start(&start("Phantom@Roguelike@StartExplore"));
+        report.sub_task_start(&start("Roguelike@StageCombatDpsEnter"));
+        report.sub_task_start(&start("Roguelike@StageTraderInvestSystem"));
+        report.update(&info(
+            "RoguelikeInvestment",
+            serde_json::json!({ "count": 12, "total": 12, "deposit": 300 }),
+        ));
+        report.sub_task_start(&start("Roguelike@ExitThenAbandon"));
+        report.sub_task_start(&start("Roguelike@StartExplore"));
+        report.update(&info(
+            "RoguelikeSettlement",
+            serde_json::json!({ "game_pass": false, "floor": 3 }),
+        ));
+        report.sub_task_start(&start("Roguelike@StartExplore"));
+
+        assert_eq!(report.runs.len(), 3);
+        assert_eq!(report.runs[0].squad, "研究分队");
+        assert_eq!(report.runs[0].stages, 1);
+        assert_eq!(report.runs[0].outcome, RogueLikeOutcome::Abandoned);
+        assert_eq!(report.runs[1].outcome, RogueLikeOutcome::Failed);
+        assert_eq!(report.runs[1].floor, Some(3));
+        assert_eq!(report.ingots_invested(), 12);
+        assert_eq!(report.deposit, Some(300));
+        assert_eq!(report.take_finished().len(), 2);
+        assert!(report.take_finished().is_empty());
+        // The failed run never got to the trader
+        assert!(!report.cap_reached(1));
+
+        report.sub_task_start(&start("Roguelike@StageTraderInvestSystem"));
+        report.sub_task_start(&start("Roguelike@StartExplore"));
+        assert!(report.runs[2].investment_reached);
+        assert!(report.cap_reached(1));
+        assert!(!report.cap_reached(2));
+
+        let mut report = RogueLikeReport::new(&rogue.investment_enabled(false));
+        report.sub_task_start(&start("Roguelike@StartExplore"));
+        report.sub_task_start(&start("Roguelike@StageTraderInvestSystem"));
+        report.sub_task_start(&start("Roguelike@StartExplore"));
+        assert!(!report.cap_reached(1));
+    }
 }
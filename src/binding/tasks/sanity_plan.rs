@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::{AsstMsg, Events, SubTaskExtraInfo, SubTaskStart};
+use crate::binding::stages::StageCatalog;
+use crate::binding::tasks::{Fight, Paused, Server, StoppedTask};
+
+/// 理智规划，按顺序尝试各关卡，跳过今日未开放的关卡，并共用理智药及源石的上限。
+///
+/// ```ignore
+/// // CE-6 开放则刷 CE-6，否则 LS-6，再否则 1-7，最多使用 2 瓶理智药
+/// let plan = SanityPlan::new(Server::CN)
+///     .stage("CE-6")
+///     .stage("LS-6")
+///     .stage("1-7")
+///     .use_medicine(2);
+/// let run = plan.append_in(&maa)?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanityPlan {
+    server: Server,
+    stages: Vec<(String, usize)>,
+    medicine: usize,
+    expiring_medicine: usize,
+    stone: usize,
+    fight: Fight<Paused>,
+}
+
+impl SanityPlan {
+    pub fn new(server: Server) -> Self {
+        SanityPlan {
+            server,
+            stages: Vec::new(),
+            medicine: 0,
+            expiring_medicine: 0,
+            stone: 0,
+            fight: Fight::new_paused().server(server),
+        }
+    }
+
+    /// 其余参数（如掉落上报、客户端版本）以此为准，关卡、次数及药、石的数量会被覆盖
+    pub fn fight(mut self, fight: Fight<Paused>) -> Self {
+        self.fight = fight.server(self.server);
+        self
+    }
+
+    /// 添加一个关卡，刷到理智耗尽为止，之后的关卡只作为它未开放时的备选
+    pub fn stage(self, stage: &str) -> Self {
+        self.stage_times(stage, 0)
+    }
+
+    /// 添加一个关卡，最多刷 `times` 次，0 表示不限次数
+    pub fn stage_times(mut self, stage: &str, times: usize) -> Self {
+        self.stages.push((stage.to_string(), times));
+        self
+    }
+
+    /// 本次规划共可使用的理智药数量
+    pub fn use_medicine(mut self, medicine: usize) -> Self {
+        self.medicine = medicine;
+        self
+    }
+
+    /// 本次规划共可使用的 48 小时内过期理智药数量
+    pub fn use_expiring_medicine(mut self, expiring_medicine: usize) -> Self {
+        self.expiring_medicine = expiring_medicine;
+        self
+    }
+
+    /// 本次规划共可使用的源石数量
+    pub fn use_stone(mut self, stone: usize) -> Self {
+        self.stone = stone;
+        self
+    }
+
+    /// The fights to append at `at`, in order, up to the first open stage without a limit
+    pub fn expand(&self, stages: &StageCatalog, at: SystemTime) -> Vec<(String, Fight<Paused>)> {
+        let mut fights = Vec::new();
+        for (stage, times) in &self.stages {
+            if !stages.is_open_at(stage, self.server, at) {
+                info!("Skipping stage {}, it is not open", stage);
+                continue;
+            }
+            let fight = self
+                .fight
+                .clone()
+                .stage(stage)
+                .stop_with_times(*times)
+                .use_medicine(self.medicine)
+                .use_expiring_medicine(self.expiring_medicine)
+                .use_stone(self.stone);
+            fights.push((stage.clone(), fight));
+            if *times == 0 {
+                break;
+            }
+        }
+        fights
+    }
+
+    /// Append the fights that are open now, the returned run keeps the budget shared
+    pub fn append_in(&self, maa: &MAAConnection) -> Result<SanityRun> {
+        let fights = self.expand(maa.stages()?, SystemTime::now());
+        if fights.is_empty() {
+            return Err(anyhow!("No stage of the sanity plan is open"));
+        }
+        for (_, fight) in &fights {
+            fight.validate(maa)?;
+        }
+        let mut planned = Vec::new();
+        for (stage, fight) in fights {
+            let id = maa.append_task(&fight)?;
+            planned.push(PlannedFight {
+                id,
+                stage,
+                fight,
+                started: false,
+            });
+        }
+        Ok(SanityRun {
+            fights: planned,
+            medicine: self.medicine,
+            expiring_medicine: self.expiring_medicine,
+            stone: self.stone,
+            spent: SanitySpent::default(),
+            last_sanity: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PlannedFight {
+    id: usize,
+    stage: String,
+    fight: Fight<Paused>,
+    started: bool,
+}
+
+/// What a sanity plan actually used
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SanitySpent {
+    pub medicine: usize,
+    pub expiring_medicine: usize,
+    pub stone: usize,
+    pub sanity: i64,
+    /// Completed runs by stage
+    pub runs: BTreeMap<String, usize>,
+}
+
+/// An appended sanity plan, passes the remaining budget on to the fights not yet started
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanityRun {
+    fights: Vec<PlannedFight>,
+    medicine: usize,
+    expiring_medicine: usize,
+    stone: usize,
+    spent: SanitySpent,
+    last_sanity: Option<i64>,
+}
+
+impl SanityRun {
+    pub fn ids(&self) -> Vec<usize> {
+        self.fights.iter().map(|f| f.id).collect()
+    }
+
+    pub fn spent(&self) -> &SanitySpent {
+        &self.spent
+    }
+
+    fn push_budget(&mut self, maa: &MAAConnection) -> Result<()> {
+        for planned in self.fights.iter_mut().filter(|f| !f.started) {
+            planned.fight = planned
+                .fight
+                .clone()
+                .use_medicine(self.medicine)
+                .use_expiring_medicine(self.expiring_medicine)
+                .use_stone(self.stone);
+            maa.set_task_params(planned.id, &planned.fight)?;
+        }
+        Ok(())
+    }
+
+    fn on_extra_info(&mut self, maa: &MAAConnection, info: SubTaskExtraInfo) -> Result<()> {
+        let details = info.details;
+        match info.what.as_str() {
+            "UseMedicine" => {
+                let count = details.count.unwrap_or(1).max(0) as usize;
+                if details.is_expiring.unwrap_or(false) {
+                    self.spent.expiring_medicine += count;
+                    self.expiring_medicine = self.expiring_medicine.saturating_sub(count);
+                } else {
+                    self.spent.medicine += count;
+                    self.medicine = self.medicine.saturating_sub(count);
+                }
+                self.push_budget(maa)?;
+            }
+            "SanityBeforeStage" => {
+                if let Some(current) = details.current_sanity {
+                    if let Some(last) = self.last_sanity.filter(|last| *last > current) {
+                        self.spent.sanity += last - current;
+                    }
+                    self.last_sanity = Some(current);
+                }
+            }
+            "StageDrops" => {
+                let taskid = info.taskid as usize;
+                if let Some(planned) = self.fights.iter().find(|f| f.id == taskid) {
+                    *self.spent.runs.entry(planned.stage.clone()).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Account for an event, returns whether the plan has ended
+    pub fn update(&mut self, maa: &MAAConnection, event: &Events) -> Result<bool> {
+        let Some(taskid) = event.params["taskid"].as_u64() else {
+            return Ok(false);
+        };
+        let Some(index) = self.fights.iter().position(|f| f.id as u64 == taskid) else {
+            return Ok(false);
+        };
+        match event.type_ {
+            AsstMsg::TaskChainStart => self.fights[index].started = true,
+            AsstMsg::SubTaskExtraInfo => {
+                let info: SubTaskExtraInfo = serde_json::from_value(event.params.clone())?;
+                self.on_extra_info(maa, info)?;
+            }
+            AsstMsg::SubTaskStart => {
+                let info: SubTaskStart = serde_json::from_value(event.params.clone())?;
+                if info.details.task.as_deref() == Some("StoneConfirm") {
+                    self.spent.stone += 1;
+                    self.stone = self.stone.saturating_sub(1);
+                    self.push_budget(maa)?;
+                }
+            }
+            AsstMsg::TaskChainStopped => return Ok(true),
+            AsstMsg::TaskChainCompleted | AsstMsg::TaskChainError => {
+                return Ok(index + 1 == self.fights.len())
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Follow the plan until its last fight ends, subscribe before starting the connection
+    pub async fn follow(
+        mut self,
+        maa: &MAAConnection,
+        mut events: broadcast::Receiver<Events>,
+    ) -> Result<SanitySpent> {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    return Err(anyhow!(
+                        "Sanity plan missed {n} events, the budget is unknown"
+                    ))
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(anyhow!("Connection closed before the sanity plan ended"))
+                }
+            };
+            if self.update(maa, &event)? {
+                return Ok(self.spent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binding::stages::Stage;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_expand() {
+        let stages: Vec<Stage> = serde_json::from_str(
+            r#"[{"stageType": "MAIN", "code": "1-7", "existence": {"CN": {"exist": true}}}]"#,
+        )
+        .unwrap();
+        let catalog = StageCatalog::from_stages(stages);
+        let plan = SanityPlan::new(Server::CN)
+            .stage_times("PR-A-2", 2)
+            .stage("CE-6")
+            .stage("1-7")
+            .stage("LS-6")
+            .use_medicine(3);
+
+        // PR-A is open on Monday in CN, CE on Tuesday
+        let monday = UNIX_EPOCH + Duration::from_secs(1685908800);
+        let tuesday = monday + Duration::from_secs(86400);
+        let stages = |at| {
+            plan.expand(&catalog, at)
+                .into_iter()
+                .map(|(stage, _)| stage)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(stages(monday), ["PR-A-2", "1-7"]);
+        assert_eq!(stages(tuesday), ["CE-6"]);
+        assert_eq!(
+            plan.expand(&catalog, tuesday)[0].1,
+            Fight::new_paused()
+                .server(Server::CN)
+                .stage("CE-6")
+                .use_medicine(3)
+        );
+    }
+}
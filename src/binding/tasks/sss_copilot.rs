@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::tasks::copilot::validate_actions;
+use crate::binding::tasks::{
+    CopilotAction, CopilotDoc, CopilotGroup, CopilotOper, Direction, Paused, Running, State,
+    StoppedTask,
+};
+
+/// 职业
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Profession {
+    #[serde(alias = "先锋")]
+    Pioneer,
+    #[serde(alias = "近卫")]
+    Warrior,
+    #[serde(alias = "重装")]
+    Tank,
+    #[serde(alias = "狙击")]
+    Sniper,
+    #[serde(alias = "术师")]
+    Caster,
+    #[serde(alias = "医疗")]
+    Medic,
+    #[serde(alias = "辅助")]
+    Support,
+    #[serde(alias = "特种")]
+    Special,
+}
+
+impl Profession {
+    /// The names of the profession in a job file
+    pub fn names(&self) -> [&str; 2] {
+        match self {
+            Profession::Pioneer => ["Pioneer", "先锋"],
+            Profession::Warrior => ["Warrior", "近卫"],
+            Profession::Tank => ["Tank", "重装"],
+            Profession::Sniper => ["Sniper", "狙击"],
+            Profession::Caster => ["Caster", "术师"],
+            Profession::Medic => ["Medic", "医疗"],
+            Profession::Support => ["Support", "辅助"],
+            Profession::Special => ["Special", "特种"],
+        }
+    }
+}
+
+/// 装备选择
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Equipment {
+    A,
+    B,
+}
+
+/// 初始策略
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SSSStrategy {
+    #[serde(rename = "优选策略")]
+    Optimal,
+    #[serde(rename = "自由策略")]
+    Free,
+}
+
+/// 部署策略，按顺序部署 `core` 或所需数量的工具人到 `location`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SSSStageStrategy {
+    pub core: Option<String>,
+    #[serde(default)]
+    pub tool_men: BTreeMap<Profession, u32>,
+    pub location: [i64; 2],
+    pub direction: Option<Direction>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SSSStage {
+    pub stage_name: String,
+    pub strategies: Vec<SSSStageStrategy>,
+    /// 是否尽可能地使用调配干员
+    #[serde(default)]
+    pub draw_as_possible: bool,
+    #[serde(default)]
+    pub actions: Vec<CopilotAction>,
+    pub retry_times: Option<u32>,
+}
+
+/// 保全派驻的作业文件
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SSSCopilotJob {
+    /// 固定为 `SSS`
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub stage_name: String,
+    pub minimum_required: Option<String>,
+    pub doc: Option<CopilotDoc>,
+    /// 开局导能元件
+    pub buff: Option<String>,
+    /// 开局装备，横着数
+    #[serde(default)]
+    pub equipment: Vec<Equipment>,
+    pub strategy: Option<SSSStrategy>,
+    #[serde(default)]
+    pub opers: Vec<CopilotOper>,
+    #[serde(default)]
+    pub groups: Vec<CopilotGroup>,
+    /// 剩余的干员按职业需要的数量
+    #[serde(default)]
+    pub tool_men: BTreeMap<Profession, u32>,
+    /// 招募时优先选择的干员或职业
+    #[serde(default)]
+    pub drops: Vec<String>,
+    #[serde(default)]
+    pub blacklist: Vec<String>,
+    pub stages: Vec<SSSStage>,
+}
+
+impl SSSCopilotJob {
+    /// Read and validate a job file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let job = std::fs::read_to_string(path)?;
+        let job: SSSCopilotJob = serde_json::from_str(&job)
+            .map_err(|e| anyhow!("Invalid SSS copilot file {}: {e}", path.display()))?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.type_ != "SSS" {
+            return Err(anyhow!("Not a SSS copilot job, type is {:?}", self.type_));
+        }
+        if self.stage_name.is_empty() {
+            return Err(anyhow!("SSS copilot job without stage name"));
+        }
+        if self.equipment.len() > 8 {
+            return Err(anyhow!("At most 8 equipment, got {}", self.equipment.len()));
+        }
+        if self.stages.is_empty() {
+            return Err(anyhow!("SSS copilot job without stages"));
+        }
+        validate_actions(&self.opers, &self.groups, &[], &[])?;
+        // Tool men and drops are recruited during the run, so they can be deployed too
+        let others = self
+            .tool_men
+            .keys()
+            .flat_map(Profession::names)
+            .chain(self.drops.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        for stage in &self.stages {
+            if stage.stage_name.is_empty() {
+                return Err(anyhow!("Stage without name"));
+            }
+            for (i, strategy) in stage.strategies.iter().enumerate() {
+                if strategy.core.is_none() && strategy.tool_men.is_empty() {
+                    return Err(anyhow!(
+                        "Strategy {i} of {} has neither core nor tool men",
+                        stage.stage_name
+                    ));
+                }
+            }
+            let mut stage_others = others.clone();
+            for strategy in &stage.strategies {
+                stage_others.extend(strategy.core.as_deref());
+                stage_others.extend(strategy.tool_men.keys().flat_map(Profession::names));
+            }
+            validate_actions(&self.opers, &self.groups, &stage_others, &stage.actions)
+                .map_err(|e| anyhow!("In stage {}: {e}", stage.stage_name))?;
+        }
+        Ok(())
+    }
+}
+
+/// 保全派驻
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
+pub struct SSSCopilot<T: State> {
+    #[serde(skip)]
+    _phantom: PhantomData<T>,
+    #[serde(skip)]
+    id: Option<usize>,
+
+    filename: String,
+    loop_times: usize,
+}
+
+impl<T: State> Default for SSSCopilot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: State> SSSCopilot<T> {
+    pub fn new() -> Self {
+        SSSCopilot {
+            _phantom: PhantomData,
+            id: None,
+            filename: String::new(),
+            loop_times: 1,
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+}
+
+impl SSSCopilot<Paused> {
+    pub fn new_paused() -> Self {
+        Self::new()
+    }
+
+    /// 作业 JSON 的文件路径，绝对、相对路径均可。不支持运行中设置
+    pub fn filename<P: AsRef<Path>>(mut self, filename: P) -> Self {
+        self.filename = filename.as_ref().to_string_lossy().to_string();
+        self
+    }
+
+    /// 循环次数，可选，默认 1。不支持运行中设置
+    pub fn loop_times(mut self, loop_times: usize) -> Self {
+        self.loop_times = loop_times;
+        self
+    }
+
+    pub fn start(self) -> SSSCopilot<Running> {
+        SSSCopilot {
+            _phantom: PhantomData,
+            id: self.id,
+            filename: self.filename,
+            loop_times: self.loop_times,
+        }
+    }
+}
+
+impl<'a> StoppedTask<'a> for SSSCopilot<Paused> {
+    fn set_id(&mut self, id: usize) {
+        self.id = Some(id);
+    }
+
+    fn name(&self) -> &'static str {
+        "SSSCopilot"
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.filename.is_empty() {
+            return Err(anyhow!("SSS copilot task without filename"));
+        }
+        if self.loop_times == 0 {
+            return Err(anyhow!("SSS copilot loop times must be positive"));
+        }
+        Ok(())
+    }
+
+    fn validate(&self, _maa: &MAAConnection) -> Result<()> {
+        self.check()?;
+        SSSCopilotJob::from_file(&self.filename)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_job() {
+        let job = r#"{
+            "type": "SSS",
+            "stage_name": "多索雷斯在建地块",
+            "opers": [{ "name": "史尔特尔", "skill": 3 }],
+            "tool_men": { "先锋": 2, "Medic": 1 },
+            "drops": ["艾雅法拉", "重装"],
+            "stages": [{
+                "stage_name": "蜂拥而上",
+                "strategies": [
+                    { "core": "棘刺", "location": [5, 5] },
+                    { "tool_men": { "Tank": 1 }, "location": [6, 5] }
+                ],
+                "actions": [
+                    { "type": "Deploy", "name": "史尔特尔", "location": [4, 4] },
+                    { "type": "Deploy", "name": "先锋", "location": [3, 4] },
+                    { "type": "Deploy", "name": "艾雅法拉", "location": [2, 4] },
+                    { "type": "Deploy", "name": "棘刺", "location": [5, 5] },
+                    { "type": "Deploy", "name": "Tank", "location": [6, 5] }
+                ]
+            }]
+        }"#;
+        let job: SSSCopilotJob = serde_json::from_str(job).unwrap();
+        job.validate().unwrap();
+
+        let mut typo = job.clone();
+        typo.stages[0].actions[2].name = "艾雅法".to_string();
+        assert!(typo.validate().is_err());
+
+        let mut not_sss = job.clone();
+        not_sss.type_ = "Copilot".to_string();
+        assert!(not_sss.validate().is_err());
+
+        let mut empty = job;
+        empty.stages[0].strategies[0].core = None;
+        assert!(empty.validate().is_err());
+    }
+}
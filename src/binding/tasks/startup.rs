@@ -1,13 +1,14 @@
 use std::marker::PhantomData;
 
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::binding::tasks::{ClientType, Paused, Running, State, StoppedTask};
 
 /// 开始唤醒
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default, deny_unknown_fields)]
 pub struct StartUp<T: State> {
     #[serde(skip)]
     _phantom: PhantomData<T>,
@@ -16,6 +17,14 @@ pub struct StartUp<T: State> {
 
     client_type: String,
     start_game_enabled: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    account_name: String,
+}
+
+impl<T: State> Default for StartUp<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: State> StartUp<T> {
@@ -25,6 +34,7 @@ impl<T: State> StartUp<T> {
             id: None,
             client_type: String::new(),
             start_game_enabled: false,
+            account_name: String::new(),
         }
     }
 
@@ -39,6 +49,13 @@ impl<T: State> StartUp<T> {
         self.start_game_enabled = start_game_enabled;
         self
     }
+
+    /// 切换到的账号，可选，默认不切换。仅支持官服及 B 服，填写已登录过的账号的一部分，
+    /// 如手机号 `123****4567` 可填 `4567`
+    pub fn set_account_name(mut self, account_name: &str) -> Self {
+        self.account_name = account_name.to_string();
+        self
+    }
 }
 
 impl StartUp<Paused> {
@@ -52,6 +69,7 @@ impl StartUp<Paused> {
             id: self.id,
             client_type: self.client_type,
             start_game_enabled: self.start_game_enabled,
+            account_name: self.account_name,
         }
     }
 }
@@ -64,4 +82,19 @@ impl<'a> StoppedTask<'a> for StartUp<Paused> {
     fn name(&self) -> &'static str {
         "StartUp"
     }
+
+    fn check(&self) -> Result<()> {
+        if self.client_type.is_empty() {
+            return Ok(());
+        }
+        let client_type = ClientType::try_from(self.client_type.as_str())?;
+        let switchable = matches!(client_type, ClientType::Official | ClientType::Bilibili);
+        if !self.account_name.is_empty() && !switchable {
+            return Err(anyhow!(
+                "Switching accounts is not supported by {}",
+                self.client_type
+            ));
+        }
+        Ok(())
+    }
 }
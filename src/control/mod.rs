@@ -0,0 +1,19 @@
+//! Local control of a running instance over a Unix domain socket.
+//!
+//! The protocol is JSON-RPC 2.0, one request or response per line.
+
+use std::path::PathBuf;
+
+pub use protocol::*;
+pub use server::*;
+
+mod protocol;
+mod server;
+
+/// `$XDG_RUNTIME_DIR/maa_rust_ui.sock`, or under the temp dir if it is not set
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("maa_rust_ui.sock")
+}
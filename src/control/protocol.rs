@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Standard JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Method {
+    /// Append a task by its MaaCore type name, e.g. `Fight`, with its raw params
+    AppendTask {
+        #[serde(rename = "type")]
+        type_: String,
+        #[serde(default)]
+        params: Value,
+    },
+    Start,
+    Stop,
+    Status,
+    /// Save a screenshot of the device to `path`
+    Screenshot {
+        path: String,
+    },
+    /// Stream every event as an `event` notification until the client disconnects
+    TailEvents,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub method: Method,
+}
+
+impl Request {
+    pub fn new(id: i64, method: Method) -> Self {
+        Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Value::from(id),
+            method,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn ok(id: Value, result: Value) -> Self {
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn err(id: Value, code: i64, message: String) -> Self {
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(RpcError { code, message }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// A server-to-client message without id, used by `tail_events`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: Value,
+}
+
+impl Notification {
+    pub fn new(method: &str, params: Value) -> Self {
+        Notification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let request: Request = serde_json::from_str(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "append_task",
+                "params": {"type": "Fight", "params": {"stage": "1-7"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::new(
+                1,
+                Method::AppendTask {
+                    type_: "Fight".to_string(),
+                    params: serde_json::json!({ "stage": "1-7" }),
+                }
+            )
+        );
+        for method in [
+            Method::Start,
+            Method::Status,
+            Method::TailEvents,
+            Method::Screenshot {
+                path: "/tmp/screen.png".to_string(),
+            },
+        ] {
+            let request = Request::new(2, method);
+            let json = serde_json::to_string(&request).unwrap();
+            assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+        }
+        assert!(
+            serde_json::from_str::<Request>(r#"{"jsonrpc": "2.0", "method": "reboot"}"#).is_err()
+        );
+
+        let ok = Response::ok(Value::from(1), serde_json::json!({ "id": 3 }));
+        assert_eq!(
+            serde_json::to_value(&ok).unwrap(),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": { "id": 3 } })
+        );
+        let err = Response::err(Value::Null, PARSE_ERROR, "bad".to_string());
+        let json = serde_json::to_string(&err).unwrap();
+        assert!(!json.contains("result"));
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), err);
+    }
+}
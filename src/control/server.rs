@@ -0,0 +1,280 @@
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use crate::binding::connection::MAAConnection;
+use crate::binding::events::Events;
+use crate::control::protocol::*;
+
+/// A request waiting to be executed against the connection.
+///
+/// `MAAConnection` can not leave the thread that created it, so the socket tasks hand
+/// requests over to whoever owns the connection, see [`ControlServer::next_call`].
+pub struct ControlCall {
+    pub method: Method,
+    reply: oneshot::Sender<Result<Value>>,
+}
+
+impl ControlCall {
+    pub async fn handle(self, maa: &MAAConnection) {
+        let ret = Self::execute(&self.method, maa).await;
+        if self.reply.send(ret).is_err() {
+            warn!("Control client left before the reply was sent");
+        }
+    }
+
+    async fn execute(method: &Method, maa: &MAAConnection) -> Result<Value> {
+        match method {
+            Method::AppendTask { type_, params } => {
+                let params = match params {
+                    Value::Null => "{}".to_string(),
+                    params => params.to_string(),
+                };
+                let id = maa.append_raw_task(type_, &params)?;
+                Ok(json!({ "id": id }))
+            }
+            Method::Start => {
+                maa.start()?;
+                Ok(Value::Bool(true))
+            }
+            Method::Stop => {
+                maa.stop();
+                Ok(Value::Bool(true))
+            }
+            Method::Status => Ok(json!({
+                "running": maa.is_running(),
+                "version": maa.get_version()?,
+                "target": maa.target(),
+                "uuid": maa.uuid().await,
+            })),
+            Method::Screenshot { path } => {
+                let image = maa.screenshot().await?;
+                std::fs::write(path, &image)?;
+                Ok(json!({ "path": path, "size": image.len() }))
+            }
+            Method::TailEvents => Err(anyhow!("tail_events is served by the socket task")),
+        }
+    }
+}
+
+pub struct ControlServer {
+    path: PathBuf,
+    calls: mpsc::Receiver<ControlCall>,
+}
+
+impl ControlServer {
+    /// Listen on `path`, replacing a stale socket file if there is one.
+    ///
+    /// Fails if another instance is listening on `path`, or if it is not a socket
+    pub async fn bind<P: AsRef<Path>>(
+        path: P,
+        events: broadcast::Receiver<Events>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        remove_stale_socket(&path).await?;
+        let listener = UnixListener::bind(&path)?;
+        info!("Control socket listening on {}", path.display());
+
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let tx = tx.clone();
+                        let events = events.resubscribe();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve_client(stream, tx, events).await {
+                                debug!("Control client disconnected: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Control socket accept error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(ControlServer { path, calls: rx })
+    }
+
+    /// Wait for the next request that needs the connection
+    pub async fn next_call(&mut self) -> Option<ControlCall> {
+        self.calls.recv().await
+    }
+}
+
+async fn remove_stale_socket(path: &Path) -> Result<()> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(anyhow!("{} exists and is not a socket", path.display()));
+    }
+    match UnixStream::connect(path).await {
+        Ok(_) => Err(anyhow!(
+            "Another instance is listening on {}",
+            path.display()
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+            debug!("Removing stale control socket {}", path.display());
+            std::fs::remove_file(path)?;
+            Ok(())
+        }
+        Err(e) => Err(anyhow!(
+            "Can't check control socket {}: {e}",
+            path.display()
+        )),
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn write_line(writer: &mut OwnedWriteHalf, value: &impl serde::Serialize) -> Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+async fn serve_client(
+    stream: UnixStream,
+    calls: mpsc::Sender<ControlCall>,
+    mut events: broadcast::Receiver<Events>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let code = match serde_json::from_str::<Value>(&line) {
+                    Ok(_) => INVALID_REQUEST,
+                    Err(_) => PARSE_ERROR,
+                };
+                write_line(
+                    &mut writer,
+                    &Response::err(Value::Null, code, e.to_string()),
+                )
+                .await?;
+                continue;
+            }
+        };
+        debug!("Control request: {:?}", request);
+
+        if request.method == Method::TailEvents {
+            write_line(&mut writer, &Response::ok(request.id, Value::Bool(true))).await?;
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let params = serde_json::to_value(event)?;
+                        write_line(&mut writer, &Notification::new("event", params)).await?;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Control client lagged behind, {} events dropped", n)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                }
+            }
+        }
+
+        let (reply, rx) = oneshot::channel();
+        calls
+            .send(ControlCall {
+                method: request.method,
+                reply,
+            })
+            .await
+            .map_err(|_| anyhow!("Control server stopped"))?;
+        let response = match rx.await {
+            Ok(Ok(result)) => Response::ok(request.id, result),
+            Ok(Err(e)) => Response::err(request.id, INTERNAL_ERROR, e.to_string()),
+            Err(_) => Response::err(request.id, INTERNAL_ERROR, "Request dropped".to_string()),
+        };
+        write_line(&mut writer, &response).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remove_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        remove_stale_socket(&path).await.unwrap();
+
+        // A live socket is kept
+        let listener = UnixListener::bind(&path).unwrap();
+        assert!(remove_stale_socket(&path).await.is_err());
+        assert!(path.exists());
+        // Nobody listens once the listener is gone, but the file stays behind
+        drop(listener);
+        remove_stale_socket(&path).await.unwrap();
+        assert!(!path.exists());
+
+        let file = dir.path().join("plain");
+        std::fs::write(&file, "data").unwrap();
+        assert!(remove_stale_socket(&file).await.is_err());
+        assert!(file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_serve_client() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (calls, mut rx) = mpsc::channel(1);
+        let (_events, events_rx) = broadcast::channel(1);
+        tokio::spawn(serve_client(server, calls, events_rx));
+        // Answer the calls the way the owner of the connection does
+        tokio::spawn(async move {
+            while let Some(call) = rx.recv().await {
+                let ret = match call.method {
+                    Method::Start => Ok(Value::Bool(true)),
+                    _ => Err(anyhow!("Not running")),
+                };
+                let _ = call.reply.send(ret);
+            }
+        });
+
+        let (reader, mut writer) = client.into_split();
+        writer
+            .write_all(
+                b"{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"start\"}\n\
+                  not json\n\
+                  {\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"status\"}\n",
+            )
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            responses.push(serde_json::from_str::<Response>(&line).unwrap());
+        }
+
+        assert_eq!(
+            responses[0],
+            Response::ok(Value::from(1), Value::Bool(true))
+        );
+        assert_eq!(responses[1].error.as_ref().unwrap().code, PARSE_ERROR);
+        assert_eq!(responses[2].id, Value::from(2));
+        assert_eq!(responses[2].error.as_ref().unwrap().code, INTERNAL_ERROR);
+    }
+}
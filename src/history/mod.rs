@@ -0,0 +1,179 @@
+//! Persistent per account storage of what tasks produced.
+//!
+//! Everything of an account lives under `<root>/<account>/`, snapshots as `<name>.json`
+//! and append only records as `<name>.jsonl`, one JSON object per line.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// A record with the unix time, in seconds, it was taken at
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamped<T> {
+    pub time: u64,
+    #[serde(flatten)]
+    pub value: T,
+}
+
+impl<T> Stamped<T> {
+    pub fn now(value: T) -> Self {
+        Stamped {
+            time: unix_time(),
+            value,
+        }
+    }
+}
+
+/// The current unix time in seconds
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn open<P: AsRef<Path>>(root: P, account: &str) -> Result<Self> {
+        if account.is_empty() || account == "." || account == ".." || account.contains(['/', '\\'])
+        {
+            return Err(anyhow!("Invalid account name: {account:?}"));
+        }
+        let dir = root.as_ref().join(account);
+        std::fs::create_dir_all(&dir)?;
+        Ok(History { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Replace the snapshot `name`
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        let path = self.dir.join(format!("{name}.json"));
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(value)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// The snapshot `name`, if it was ever saved
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        let path = self.dir.join(format!("{name}.json"));
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let value = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&value)?))
+    }
+
+    /// Add a record at the end of `name`
+    pub fn append<T: Serialize>(&self, name: &str, record: &T) -> Result<()> {
+        let path = self.dir.join(format!("{name}.jsonl"));
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// Every record of `name`, oldest first
+    pub fn records<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
+        let path = self.dir.join(format!("{name}.jsonl"));
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let mut records = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_open() {
+        let root = tempfile::tempdir().unwrap();
+        let history = History::open(root.path(), DEFAULT_ACCOUNT).unwrap();
+        assert_eq!(history.dir(), root.path().join(DEFAULT_ACCOUNT));
+        assert!(history.dir().is_dir());
+        // Opening again keeps what is there
+        history.append("runs", &1).unwrap();
+        let history = History::open(root.path(), DEFAULT_ACCOUNT).unwrap();
+        assert_eq!(history.records::<i64>("runs").unwrap(), [1]);
+
+        for account in ["", ".", "..", "a/b", "..\\c"] {
+            assert!(History::open(root.path(), account).is_err(), "{account:?}");
+        }
+        assert!(History::open(root.path(), "名字 with spaces").is_ok());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let root = tempfile::tempdir().unwrap();
+        let history = History::open(root.path(), "main").unwrap();
+        assert_eq!(
+            history.load::<BTreeMap<String, i64>>("depot").unwrap(),
+            None
+        );
+
+        let depot = BTreeMap::from([("30012".to_string(), 5)]);
+        history.save("depot", &depot).unwrap();
+        assert_eq!(history.load("depot").unwrap(), Some(depot.clone()));
+
+        let depot = BTreeMap::from([("30013".to_string(), 2)]);
+        history.save("depot", &depot).unwrap();
+        assert_eq!(history.load("depot").unwrap(), Some(depot));
+        assert!(!history.dir().join("depot.json.tmp").exists());
+
+        // A snapshot of another shape is an error, not a missing one
+        assert!(history.load::<Vec<String>>("depot").is_err());
+    }
+
+    #[test]
+    fn test_records() {
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        struct Run {
+            floor: i64,
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        let history = History::open(root.path(), "main").unwrap();
+        assert!(history.records::<Run>("runs").unwrap().is_empty());
+
+        std::fs::write(history.dir().join("empty.jsonl"), "").unwrap();
+        assert!(history.records::<Run>("empty").unwrap().is_empty());
+
+        let runs = [
+            Stamped::now(Run { floor: 3 }),
+            Stamped::now(Run { floor: 5 }),
+        ];
+        for run in &runs {
+            history.append("runs", run).unwrap();
+        }
+        assert_eq!(history.records::<Stamped<Run>>("runs").unwrap(), runs);
+        let line = std::fs::read_to_string(history.dir().join("runs.jsonl")).unwrap();
+        assert!(line.starts_with(&format!("{{\"time\":{},\"floor\":3}}", runs[0].time)));
+
+        // Other accounts have their own records
+        let alt = History::open(root.path(), "alt").unwrap();
+        assert!(alt.records::<Run>("runs").unwrap().is_empty());
+    }
+}
@@ -1,5 +1,10 @@
 #![feature(os_str_bytes)]
 
+pub mod accounts;
 pub mod binding;
+#[cfg(unix)]
+pub mod control;
+pub mod history;
+pub mod metrics;
 pub mod updater;
 pub mod gui;
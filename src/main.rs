@@ -1,10 +1,11 @@
-use std::thread;
-
-use log::info;
+use log::{error, info};
 
 use maa_rust_ui::binding::connection::MAABuilder;
 use maa_rust_ui::binding::options::{MAAOption, TouchMode};
 use maa_rust_ui::binding::tasks::*;
+#[cfg(unix)]
+use maa_rust_ui::control::{default_socket_path, ControlServer};
+use maa_rust_ui::metrics::Metrics;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -49,8 +50,16 @@ async fn main() {
 
     let _mall = Mall::new()
         .shopping(true)
-        .buy_first(vec![ShopItem::LMD, ShopItem::RecruitmentPermit], &server)
-        .blacklist(vec![ShopItem::CarbonStick, ShopItem::FurniturePart], &server)
+        .buy_first(
+            vec![ShopItem::LMD, ShopItem::RecruitmentPermit],
+            m.items(server),
+        )
+        .unwrap()
+        .blacklist(
+            vec![ShopItem::CarbonStick, ShopItem::FurniturePart],
+            m.items(server),
+        )
+        .unwrap()
         .force_buy_when_full(true)
         .append_in(&mut m)
         .unwrap()
@@ -62,11 +71,43 @@ async fn main() {
         VERSION
     );
 
+    // Metrics are optional, a bad address only disables them
+    if let Ok(addr) = std::env::var("MAA_METRICS_ADDR") {
+        let metrics = Metrics::new();
+        let served = match addr.parse() {
+            Ok(addr) => metrics.serve(addr).await,
+            Err(e) => Err(e.into()),
+        };
+        match served {
+            Ok(()) => metrics.watch(m.subscribe()),
+            Err(e) => error!("Not serving metrics on {}: {}", addr, e),
+        }
+    }
+
+    #[cfg(unix)]
+    let mut control = ControlServer::bind(default_socket_path(), m.subscribe())
+        .await
+        .unwrap();
+
     m.start().unwrap();
 
-    while m.is_running() {
-        thread::sleep(std::time::Duration::from_secs(1));
+    // Serve the control socket after the tasks are done too, they can append and start more
+    #[cfg(unix)]
+    {
+        let shutdown = tokio::signal::ctrl_c();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                Some(call) = control.next_call() => call.handle(&m).await,
+                _ = &mut shutdown => break,
+            }
+        }
     }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
+    m.stop();
     info!("MAA have stopped");
+    #[cfg(unix)]
+    drop(control);
     std::process::exit(0);
 }
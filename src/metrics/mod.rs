@@ -0,0 +1,369 @@
+//! Prometheus metrics derived from the events MaaCore sends back.
+//!
+//! Nothing is collected unless [`Metrics::watch`] is called, and nothing is exposed
+//! unless [`Metrics::serve`] is called.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+
+use crate::binding::events::*;
+
+/// Name, type and help text of every exported metric
+const FAMILIES: &[(&str, &str, &str)] = &[
+    (
+        "maa_task_chains_started_total",
+        "counter",
+        "Task chains started, by task chain type",
+    ),
+    (
+        "maa_task_chains_completed_total",
+        "counter",
+        "Task chains completed, by task chain type",
+    ),
+    (
+        "maa_task_chains_errored_total",
+        "counter",
+        "Task chains errored, by task chain type",
+    ),
+    (
+        "maa_fight_sanity_used_total",
+        "counter",
+        "Sanity used by Fight, measured between two sanity readings",
+    ),
+    (
+        "maa_fight_medicine_used_total",
+        "counter",
+        "Sanity potions used by Fight",
+    ),
+    (
+        "maa_fight_stones_used_total",
+        "counter",
+        "Originite primes used by Fight",
+    ),
+    ("maa_sanity", "gauge", "Last sanity reading"),
+    ("maa_sanity_max", "gauge", "Last max sanity reading"),
+    (
+        "maa_items_dropped_total",
+        "counter",
+        "Items dropped in battle, by item id",
+    ),
+    (
+        "maa_recruit_results_total",
+        "counter",
+        "Recruitment results, by star level",
+    ),
+    (
+        "maa_connection_reconnects_total",
+        "counter",
+        "Successful reconnections to the device",
+    ),
+    (
+        "maa_async_call_cost_milliseconds",
+        "summary",
+        "Cost of async calls such as Screencap, by call",
+    ),
+];
+
+#[derive(Default)]
+struct Registry {
+    /// (metric name, rendered labels such as `{level="4"}`) to value
+    samples: BTreeMap<(&'static str, String), f64>,
+    last_sanity: Option<i64>,
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", k, v)
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    if labels.is_empty() {
+        labels
+    } else {
+        format!("{{{}}}", labels)
+    }
+}
+
+impl Registry {
+    fn inc(&mut self, name: &'static str, labels: &[(&str, &str)], by: f64) {
+        *self
+            .samples
+            .entry((name, render_labels(labels)))
+            .or_default() += by;
+    }
+
+    fn set(&mut self, name: &'static str, labels: &[(&str, &str)], value: f64) {
+        self.samples.insert((name, render_labels(labels)), value);
+    }
+
+    fn observe_sub_task_extra_info(&mut self, info: SubTaskExtraInfo) {
+        let details = info.details;
+        match info.what.as_str() {
+            "StageDrops" => {
+                for drop in details.drops.unwrap_or_default() {
+                    self.inc(
+                        "maa_items_dropped_total",
+                        &[("item_id", &drop.item_id)],
+                        drop.quantity as f64,
+                    );
+                }
+            }
+            "UseMedicine" => {
+                let expiring = details.is_expiring.unwrap_or(false).to_string();
+                self.inc(
+                    "maa_fight_medicine_used_total",
+                    &[("expiring", &expiring)],
+                    details.count.unwrap_or(1) as f64,
+                );
+            }
+            "SanityBeforeStage" => {
+                let Some(current) = details.current_sanity else {
+                    return;
+                };
+                if let Some(last) = self.last_sanity.filter(|last| *last > current) {
+                    self.inc("maa_fight_sanity_used_total", &[], (last - current) as f64);
+                }
+                self.last_sanity = Some(current);
+                self.set("maa_sanity", &[], current as f64);
+                if let Some(max) = details.max_sanity {
+                    self.set("maa_sanity_max", &[], max as f64);
+                }
+            }
+            "RecruitResult" => {
+                if let Some(level) = details.level {
+                    self.inc(
+                        "maa_recruit_results_total",
+                        &[("level", &level.to_string())],
+                        1.0,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn observe(&mut self, event: &Events) {
+        fn parse<T: DeserializeOwned>(params: &Value) -> Option<T> {
+            match serde_json::from_value(params.clone()) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    debug!("Metrics skipped an event: {}", e);
+                    None
+                }
+            }
+        }
+
+        match event.type_ {
+            AsstMsg::TaskChainStart => {
+                if let Some(info) = parse::<TaskChainStart>(&event.params) {
+                    let labels = [("taskchain", info.taskchain.as_str())];
+                    self.inc("maa_task_chains_started_total", &labels, 1.0);
+                }
+            }
+            AsstMsg::TaskChainCompleted => {
+                if let Some(info) = parse::<TaskChainCompleted>(&event.params) {
+                    let labels = [("taskchain", info.taskchain.as_str())];
+                    self.inc("maa_task_chains_completed_total", &labels, 1.0);
+                }
+            }
+            AsstMsg::TaskChainError => {
+                if let Some(info) = parse::<TaskChainError>(&event.params) {
+                    let labels = [("taskchain", info.taskchain.as_str())];
+                    self.inc("maa_task_chains_errored_total", &labels, 1.0);
+                }
+            }
+            AsstMsg::SubTaskStart => {
+                if let Some(info) = parse::<SubTaskStart>(&event.params) {
+                    if info.taskchain == "Fight"
+                        && info.details.task.as_deref() == Some("StoneConfirm")
+                    {
+                        self.inc("maa_fight_stones_used_total", &[], 1.0);
+                    }
+                }
+            }
+            AsstMsg::SubTaskExtraInfo => {
+                if let Some(info) = parse::<SubTaskExtraInfo>(&event.params) {
+                    self.observe_sub_task_extra_info(info);
+                }
+            }
+            AsstMsg::ConnectionInfo => {
+                if let Some(info) = parse::<ConnectionInfo>(&event.params) {
+                    if ConnectionInfoWhat::from(info.what.as_str())
+                        == ConnectionInfoWhat::Reconnected
+                    {
+                        self.inc("maa_connection_reconnects_total", &[], 1.0);
+                    }
+                }
+            }
+            AsstMsg::AsyncCallInfo => {
+                if let Some(info) = parse::<AsyncCallInfo>(&event.params) {
+                    let labels = [("what", info.what.as_str())];
+                    let cost = info.details.cost as f64;
+                    self.inc("maa_async_call_cost_milliseconds_sum", &labels, cost);
+                    self.inc("maa_async_call_cost_milliseconds_count", &labels, 1.0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for (family, kind, help) in FAMILIES {
+            writeln!(out, "# HELP {} {}", family, help).unwrap();
+            writeln!(out, "# TYPE {} {}", family, kind).unwrap();
+            for ((name, labels), value) in &self.samples {
+                let belongs = match name.strip_prefix(family) {
+                    Some(suffix) => suffix.is_empty() || suffix == "_sum" || suffix == "_count",
+                    None => false,
+                };
+                if belongs {
+                    writeln!(out, "{}{} {}", name, labels, value).unwrap();
+                }
+            }
+        }
+        out
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, event: &Events) {
+        self.registry.lock().unwrap().observe(event);
+    }
+
+    /// Render every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        self.registry.lock().unwrap().render()
+    }
+
+    /// Keep observing events from `MAAConnection::subscribe` in the background
+    pub fn watch(&self, mut events: broadcast::Receiver<Events>) {
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => metrics.observe(&event),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Metrics lagged behind, {} events dropped", n)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Serve `/metrics` over plain HTTP on `addr` in the background
+    pub async fn serve(&self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Serving metrics on http://{}/metrics", addr);
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = metrics.respond(stream).await {
+                        debug!("Metrics request failed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    async fn respond(&self, mut stream: TcpStream) -> Result<()> {
+        let mut buff = vec![0u8; 1024];
+        let mut len = 0;
+        while !buff[..len].windows(4).any(|w| w == b"\r\n\r\n") && len < buff.len() {
+            let n = stream.read(&mut buff[len..]).await?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        let request = String::from_utf8_lossy(&buff[..len]);
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+        let (status, content_type, body) = if path == "/metrics" {
+            ("200 OK", "text/plain; version=0.0.4", self.render())
+        } else {
+            ("404 Not Found", "text/plain", "Not Found\n".to_string())
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn event(type_: AsstMsg, params: Value) -> Events {
+        Events { type_, params }
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.observe(&event(
+            AsstMsg::TaskChainStart,
+            json!({"taskchain": "Fight", "taskid": 1, "uuid": ""}),
+        ));
+        for current in [100, 82, 64] {
+            metrics.observe(&event(
+                AsstMsg::SubTaskExtraInfo,
+                json!({
+                    "class": "asst::FightTask", "subtask": "", "taskchain": "Fight",
+                    "taskid": 1, "uuid": "", "what": "SanityBeforeStage",
+                    "details": {"current_sanity": current, "max_sanity": 135}
+                }),
+            ));
+        }
+        metrics.observe(&event(
+            AsstMsg::AsyncCallInfo,
+            json!({"uuid": "", "what": "Screencap", "async_call_id": 2,
+                   "details": {"ret": true, "cost": 42}}),
+        ));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("maa_task_chains_started_total{taskchain=\"Fight\"} 1\n"));
+        assert!(rendered.contains("maa_fight_sanity_used_total 36\n"));
+        assert!(rendered.contains("maa_sanity 64\n"));
+        assert!(rendered.contains("maa_sanity_max 135\n"));
+        assert!(rendered.contains("maa_async_call_cost_milliseconds_sum{what=\"Screencap\"} 42\n"));
+        assert!(rendered.contains("maa_async_call_cost_milliseconds_count{what=\"Screencap\"} 1\n"));
+    }
+}